@@ -5,28 +5,106 @@ use macroquad::audio::*;
 
 use crate::ui::Ui;
 use crate::game::Game;
+use crate::game::world::{LevelLoadError, RunStatus, Start};
+use crate::game::statistics::Statistics;
+use crate::editor::Editor;
 use crate::game::resource_manager::*;
 
 
+/// Message of the error screen
+#[derive(Debug, Default)]
+pub struct ErrorScreen {
+	message: Option<String>
+}
+
+impl ErrorScreen {
+	/// State of the ui showing the message
+	pub const STATE: &'static str = "Error";
+
+	pub fn show(&mut self, message: String) {
+		self.message = Some(message);
+	}
+
+	pub fn message(&self) -> Option<&str> { self.message.as_deref() }
+
+	/// Forgets the message once its screen is left, switching to it is
+	/// not enough
+	pub fn state_changed(&mut self, from: &str) {
+		if from == Self::STATE {
+			self.message = None;
+		}
+	}
+}
+
 /// Highest structure in the "ownership tree" of this project
 /// Manages everything
 pub struct Application {
 	ui: Ui,
 	game: Option<Game>,
+	editor: Option<Editor>,
 	resource_manager: ResourceManager,
-	notif_cooldown: i32
+	notif_cooldown: i32,
+	error: ErrorScreen,
+	statistics: Statistics,
+	/// Where the next level played starts, see `Application::play()`
+	start: Option<Start>
+}
+
+impl Default for Application {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl Application {
 	pub fn new() -> Self {
+		let statistics = Statistics::load();
+		let mut ui = Ui::default();
+		ui.refresh_levels(&statistics);
+
 		Self {
-			ui: Default::default(),
+			ui,
 			game: None,
+			editor: None,
 			resource_manager: ResourceManager::new(),
-			notif_cooldown: 0
+			notif_cooldown: 0,
+			error: ErrorScreen::default(),
+			statistics,
+			start: None
+		}
+	}
+
+	/// Starts directly in `level`, at `start` if any
+	pub fn play(mut self, level: &str, start: Option<Start>) -> Self {
+		self.ui.request_level(level);
+		self.start = start;
+		self
+	}
+
+	fn save_statistics(statistics: &Statistics) {
+		if let Err(e) = statistics.save() {
+			eprintln!("Application: Cannot save statistics: {e}");
 		}
 	}
 
+	/// Counts a new attempt at the level being played
+	fn record_attempt(&mut self) {
+		if let Some(game) = &self.game {
+			if !game.is_watching() {
+				self.statistics.level_mut(game.level()).attempts += 1;
+				Self::save_statistics(&self.statistics);
+			}
+		}
+	}
+
+	/// Drops the current game and shows `e` on the error screen
+	fn fail(&mut self, e: LevelLoadError) {
+		eprintln!("{e}");
+		self.error.show(e.to_string());
+		self.game = None;
+		self.ui.set_state(ErrorScreen::STATE);
+	}
+
 	/// Main loop
 	pub async fn run(&mut self) {
 
@@ -41,9 +119,34 @@ impl Application {
 
 			// Starting level if requested
 			if let Some(level) = self.ui.get_requested_level() {
-				self.game = Some(Game::new(&level, &mut self.resource_manager).await);
+				let game = match (Game::new(&level, &mut self.resource_manager).await, self.start.take()) {
+					(Ok(mut game), Some(start)) => game.start_at(start).map(|()| game),
+					(game, _) => game
+				};
+				match game {
+					Ok(game) => {
+						self.game = Some(game);
+						self.record_attempt();
+					},
+					Err(e) => self.fail(e)
+				}
+			} else if let Some(level) = self.ui.get_requested_replay() {
+				match Game::watch(&level, &mut self.resource_manager).await {
+					Ok(game) => self.game = Some(game),
+					Err(e) => self.fail(e)
+				}
+			} else if let Some(level) = self.ui.get_requested_edit() {
+				match Editor::open(&level) {
+					Ok(editor) => self.editor = Some(editor),
+					Err(e) => self.fail(e)
+				}
 			} else if last_state != current_state {
 				self.game = None;
+				self.editor = None;
+				self.error.state_changed(&last_state);
+				if current_state == "Play" || current_state == "Editor" {
+					self.ui.refresh_levels(&self.statistics);
+				}
 			}
 
 			// Changing volume if requested
@@ -57,22 +160,63 @@ impl Application {
 			if let Some(game) = &mut self.game {
 				game.update();
 
-				// Restart level
-				if game.is_finished() && is_key_pressed(KeyCode::R) {
-					game.reload(&mut self.resource_manager).await;
+				if let Some(report) = game.take_report() {
+					let stats = self.statistics.level_mut(game.level());
+					match report.status {
+						RunStatus::Dead => stats.add_death(report.position),
+						RunStatus::Complete => if stats.best_time.is_none_or(|best| report.time < best) {
+							stats.best_time = Some(report.time);
+						},
+						RunStatus::Playing => {}
+					}
+					Self::save_statistics(&self.statistics);
+				}
+
+				// Restart level, from last checkpoint if any
+				if game.is_finished() && game.restart_requested()
+				&& (game.is_complete() || !game.respawn()) {
+					if let Err(e) = game.reload(&mut self.resource_manager).await {
+						self.fail(e);
+					} else {
+						self.record_attempt();
+					}
 				}
 			}
 			
+			if let Some(editor) = &mut self.editor {
+				editor.update(&mut self.resource_manager).await;
+			}
+
 			self.ui.update();
 
-			clear_background(BLACK);
+			clear_background(match (&self.game, &self.editor) {
+				(Some(game), _) => game.background(),
+				(None, Some(editor)) => editor.background(),
+				(None, None) => BLACK
+			});
 				
 			if let Some(game) = &mut self.game {
 				game.draw();
-				if game.is_finished() {
+				if game.is_complete() {
+					draw_rectangle(0., 0., screen_width(), screen_height(), Color::new(0., 0., 0., 0.5));
+					draw_text("Level complete", 10., 400., 64., GREEN);
+					draw_text("Press R to play again", 10., 440., 32., WHITE);
+				} else if game.is_finished() && game.has_checkpoint() {
+					draw_text("Press R to respawn", 10., 440., 48., RED);
+				} else if game.is_finished() {
 					draw_text("Press R to restart", 10., 440., 48., RED);
 				}
 			}
+
+			if let Some(editor) = &mut self.editor {
+				editor.draw();
+			}
+
+			if let Some(error) = self.error.message() {
+				for (i, line) in error.lines().enumerate() {
+					draw_text(&line.replace('\t', "    "), 10., 100. + i as f32 * 20., 20., RED);
+				}
+			}
 				
 			self.ui.draw();
 						
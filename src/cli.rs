@@ -0,0 +1,121 @@
+//! Command line interface
+
+use macroquad::prelude::*;
+
+use crate::game::world::{World, Start};
+use crate::game::resource_manager::ResourceManager;
+use crate::game::headless::block_on;
+
+pub const USAGE: &str = "\
+Usage: runner [OPTIONS] [COMMAND]
+
+Commands:
+  (none)                Opens the game
+  check <file.lvl>...   Checks level files without opening any window
+  help                  Prints this message
+
+Options:
+  --levels-dir <dir>    Reads levels from <dir> instead of res/levels
+  --level <file.lvl>    Starts playing <file.lvl> right away
+  --checkpoint <n>      Starts the level at its n-th checkpoint
+  --at <x>,<y>          Starts the level at this position, in pixels";
+
+/// What the program was asked to do
+#[derive(Debug, PartialEq)]
+pub enum Command {
+	/// Opens the game, in the given level if any
+	Play (Option<String>, Option<Start>),
+	Check (Vec::<String>),
+	Help
+}
+
+/// Parsed command line
+#[derive(Debug, PartialEq)]
+pub struct Arguments {
+	pub command: Command,
+	pub levels_dir: Option<String>
+}
+
+impl Arguments {
+	/// Parses arguments, without the program name
+	pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+		let mut args = args.into_iter();
+		let mut positional = Vec::<String>::new();
+		let mut levels_dir = None;
+		let mut level = None;
+		let mut start = None;
+
+		while let Some(arg) = args.next() {
+			let mut value = || args.next().ok_or_else(|| format!("`{arg}` expects a value"));
+			match arg.as_str() {
+				"--levels-dir" => levels_dir = Some(value()?),
+				"--level" => level = Some(value()?),
+				"--checkpoint" => {
+					let v = value()?;
+					start = Some(Start::Checkpoint(v
+						.parse()
+						.map_err(|_| format!("invalid checkpoint `{v}`"))?
+					));
+				},
+				"--at" => {
+					let v = value()?;
+					start = Some(Start::Position(Self::position(&v)
+						.ok_or_else(|| format!("invalid position `{v}`, expected <x>,<y>"))?
+					));
+				},
+				_ if arg.starts_with("--") && arg != "--help" => return Err(format!("unknown option `{arg}`")),
+				_ => positional.push(arg)
+			}
+		}
+
+		if start.is_some() && level.is_none() {
+			return Err("a start position needs `--level`".to_string());
+		}
+
+		let mut positional = positional.into_iter();
+		let command = match positional.next().as_deref() {
+			None => Command::Play(level, start),
+			Some(_) if level.is_some() => return Err("`--level` only applies to playing".to_string()),
+			Some("check") => {
+				let files: Vec::<String> = positional.collect();
+				if files.is_empty() {
+					return Err("`check` expects at least one level file".to_string());
+				}
+				Command::Check(files)
+			},
+			Some("help" | "--help" | "-h") => Command::Help,
+			Some(other) => return Err(format!("unknown command `{other}`"))
+		};
+
+		Ok(Self {command, levels_dir})
+	}
+
+	/// Parses `x,y`
+	fn position(s: &str) -> Option<Vec2> {
+		let (x, y) = s.split_once(',')?;
+		Some(vec2(x.trim().parse().ok()?, y.trim().parse().ok()?))
+	}
+}
+
+/// Tokenizes and interprets every file, printing diagnostics on the error output.
+/// Included files are read relative to the file including them.
+/// Returns the number of files with errors.
+pub fn check(files: &[String]) -> usize {
+	let mut errors = 0;
+
+	for file in files {
+		// Includes are relative to the checked file
+		if let Err(e) = block_on(World::from_file(file, &mut ResourceManager::null())) {
+			eprintln!("{}\n", e.to_string().trim_end());
+			errors += 1;
+		}
+	}
+
+	eprintln!(
+		"checked {} level{}, {} with errors",
+		files.len(),
+		if files.len() == 1 { "" } else { "s" },
+		errors
+	);
+	errors
+}
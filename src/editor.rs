@@ -0,0 +1,413 @@
+//! In-game level editor
+
+pub mod history;
+
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+use crate::game::world::{World, LevelLoadError};
+use crate::game::world::blueprint::*;
+use crate::game::resource_manager::ResourceManager;
+use crate::game::input::*;
+use crate::game::headless::block_on;
+
+use history::*;
+
+/// What a mouse drag is doing
+#[derive(Clone, Copy)]
+enum Drag {
+	/// `added` when the piece was placed by this drag
+	Move {piece: usize, offset: Vec2, from: Vec2, added: bool},
+	Resize {piece: usize, from: Vec2},
+	Pan {last: Vec2}
+}
+
+/// Places, moves, resizes and deletes objects on the level grid and
+/// saves them back to the level file
+pub struct Editor {
+	level: String,
+	blueprint: Blueprint,
+	history: History,
+	camera: Camera2D,
+	tool: usize,
+	drag: Option<Drag>,
+	playtest: Option<World>,
+	playtest_from: Vec2,
+	input: Keyboard,
+	message: String,
+	/// Whether the level source has constructs a blueprint cannot write back
+	flattened: bool
+}
+
+impl Editor {
+	/// Objects that can be placed, selected with number keys or cycled through with Tab
+	const TOOLS: [&'static str; 12] = ["Wall", "Spike", "Player", "Goal", "Checkpoint", "Platform", "JumpPad", "GravityPortal", "SpeedPortal", "OneWayPlatform", "SlopeUp", "SlopeDown"];
+
+	/// Screen area covered by the menu, ignored by the mouse
+	const MENU_HEIGHT: f32 = 70.;
+
+	/// Size of the handle at the bottom right corner of pieces, in pixels
+	const HANDLE: f32 = 8.;
+
+	/// Opens `level`, creating an empty one if it does not exist.
+	/// No texture nor sound is needed until playtesting.
+	pub fn open(level: &str) -> Result<Self, LevelLoadError> {
+		let mut flattened = false;
+		let blueprint = if Path::new(&format!("{}/{level}", World::levels_dir())).exists() {
+			let source = World::read(level)?;
+			flattened = Self::flattens(&source);
+			Blueprint::from(&block_on(World::from_source(level, &source, &mut ResourceManager::null()))?)
+		} else {
+			let mut r = Blueprint::new(vec2(32., 32.));
+			let mut player = Piece::new("Player", Vec2::ZERO, r.unit);
+			player.speed.x = 5.;
+			r.pieces.push(player);
+			r.pieces.push(Piece::new("Wall", vec2(0., r.unit.y), r.unit * vec2(10., 1.)));
+			r
+		};
+
+		Ok(Self {
+			level: level.to_owned(),
+			blueprint,
+			history: History::new(),
+			camera: Camera2D {
+				zoom: vec2(2. / screen_width(), 2. / screen_height()),
+				target: vec2(screen_width(), screen_height()) * 0.4,
+				..Default::default()
+			},
+			tool: 0,
+			drag: None,
+			playtest: None,
+			playtest_from: Vec2::ZERO,
+			input: Keyboard,
+			message: String::new(),
+			flattened
+		})
+	}
+
+	/// Whether `source` includes files, declares prefabs, loops, constants
+	/// or comments, all lost when saving its blueprint
+	fn flattens(source: &str) -> bool {
+		source.lines().any(|line| {
+			let line = line.trim_start();
+			line.starts_with('#')
+				|| matches!(line.split_whitespace().next(), Some("include" | "prefab" | "place" | "repeat" | "let"))
+		})
+	}
+
+	fn players(&self) -> usize {
+		self.blueprint.pieces.iter().filter(|p| p.keyword == "Player").count()
+	}
+
+	pub fn is_playtesting(&self) -> bool { self.playtest.is_some() }
+
+	pub fn background(&self) -> Color { self.blueprint.metadata.background }
+
+	pub async fn update(&mut self, rm: &mut ResourceManager) {
+		if let Some(world) = &mut self.playtest {
+			world.update(get_frame_time(), &mut self.input);
+
+			if self.input.pause_requested() {
+				self.playtest = None;
+			} else if self.input.restart_requested() {
+				self.playtest(self.playtest_from, rm).await;
+			}
+			return;
+		}
+
+		let mouse = Vec2::from(mouse_position());
+		let cursor = self.camera.screen_to_world(mouse);
+
+		// Keyboard shortcuts
+		for (i, key) in [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9, KeyCode::Key0].iter().enumerate() {
+			if is_key_pressed(*key) {
+				self.tool = i;
+			}
+		}
+		if is_key_pressed(KeyCode::Tab) {
+			self.tool = (self.tool + 1) % Self::TOOLS.len();
+		}
+		let control = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+		let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+		if control && is_key_pressed(KeyCode::S) {
+			self.save();
+		}
+		if self.drag.is_none() {
+			if control && (is_key_pressed(KeyCode::Y) || shift && is_key_pressed(KeyCode::Z)) {
+				self.history.redo(&mut self.blueprint);
+			} else if control && is_key_pressed(KeyCode::Z) {
+				self.history.undo(&mut self.blueprint);
+			}
+			if !control && is_key_pressed(KeyCode::C) {
+				match self.blueprint.piece_at(cursor) {
+					// Players have to be declared first
+					Some(i) if Self::TOOLS[self.tool] == "Player" && self.blueprint.pieces[i].keyword != "Player" => {
+						self.message = "Place players with the Player tool".to_string();
+					},
+					Some(i) => {
+						let command = Command::ChangeKind {
+							piece: i,
+							from: self.blueprint.pieces[i].keyword.clone(),
+							to: Self::TOOLS[self.tool].to_owned()
+						};
+						self.history.execute(command, &mut self.blueprint);
+					},
+					None => {}
+				}
+			}
+			for (key, factor) in [(KeyCode::LeftBracket, 0.5), (KeyCode::RightBracket, 2.)] {
+				if is_key_pressed(key) {
+					let from = self.blueprint.unit;
+					let to = (from * factor).max(Vec2::ONE).round();
+					self.history.execute(Command::ChangeUnit {from, to}, &mut self.blueprint);
+				}
+			}
+		}
+		if is_key_pressed(KeyCode::P) {
+			self.playtest(self.snap(cursor), rm).await;
+			return;
+		}
+
+		let pan = vec2(
+			is_key_down(KeyCode::Right) as i32 as f32 - is_key_down(KeyCode::Left) as i32 as f32,
+			is_key_down(KeyCode::Down) as i32 as f32 - is_key_down(KeyCode::Up) as i32 as f32
+		);
+		self.camera.target += pan * 10. / (self.camera.zoom.x * screen_width() / 2.);
+
+		let wheel = mouse_wheel().1;
+		if wheel != 0. {
+			let factor = if wheel > 0. { 1.1 } else { 1. / 1.1 };
+			self.camera.zoom *= factor;
+		}
+
+		// Mouse edition
+		if mouse.y < Self::MENU_HEIGHT && self.drag.is_none() {
+			return;
+		}
+
+		if is_mouse_button_pressed(MouseButton::Left) {
+			self.drag = match self.blueprint.piece_at(cursor) {
+				Some(i) if self.on_handle(i, cursor) => Some(Drag::Resize {piece: i, from: self.blueprint.pieces[i].size}),
+				Some(i) => {
+					let from = self.blueprint.pieces[i].position;
+					Some(Drag::Move {piece: i, offset: from - cursor, from, added: false})
+				},
+				// Only one player, moved instead of placed again
+				None if Self::TOOLS[self.tool] == "Player" && self.players() > 0 => {
+					let i = self.blueprint.pieces.iter().position(|p| p.keyword == "Player").unwrap();
+					let from = self.blueprint.pieces[i].position;
+					Some(Drag::Move {piece: i, offset: self.snap(cursor) - cursor, from, added: false})
+				},
+				None => {
+					let piece = Piece::new(Self::TOOLS[self.tool], self.snap(cursor), self.blueprint.unit);
+					let from = piece.position;
+					// Only objects declared after players collide with them
+					let i = if piece.keyword == "Player" { 0 } else { self.blueprint.pieces.len() };
+					// Recorded with its final position once dropped
+					Command::Add(i, piece).apply(&mut self.blueprint);
+					Some(Drag::Move {piece: i, offset: from - cursor, from, added: true})
+				}
+			};
+		} else if is_mouse_button_pressed(MouseButton::Middle) {
+			self.drag = Some(Drag::Pan {last: mouse});
+		} else if is_mouse_button_pressed(MouseButton::Right) {
+			if let Some(i) = self.blueprint.piece_at(cursor) {
+				let piece = self.blueprint.pieces[i].clone();
+				self.history.execute(Command::Remove(i, piece), &mut self.blueprint);
+			}
+		}
+
+		match self.drag {
+			Some(Drag::Move {piece, offset, ..}) => {
+				// Rounding to the closest cell
+				self.blueprint.pieces[piece].position = self.snap(cursor + offset + self.blueprint.unit * 0.5);
+			},
+			Some(Drag::Resize {piece, ..}) => {
+				let unit = self.blueprint.unit;
+				let p = &mut self.blueprint.pieces[piece];
+				p.size = ((cursor - p.position) / unit).ceil().max(Vec2::ONE) * unit;
+			},
+			Some(Drag::Pan {last}) => {
+				self.camera.target -= cursor - self.camera.screen_to_world(last);
+				self.drag = Some(Drag::Pan {last: mouse});
+			},
+			None => {}
+		}
+
+		if is_mouse_button_released(MouseButton::Left) || is_mouse_button_released(MouseButton::Middle) {
+			// The whole drag is a single step of the history
+			match self.drag.take() {
+				Some(Drag::Move {piece, added: true, ..}) => {
+					self.history.record(Command::Add(piece, self.blueprint.pieces[piece].clone()));
+				},
+				Some(Drag::Move {piece, from, ..}) => {
+					let to = self.blueprint.pieces[piece].position;
+					self.history.record(Command::Move {piece, from, to});
+				},
+				Some(Drag::Resize {piece, from}) => {
+					let to = self.blueprint.pieces[piece].size;
+					self.history.record(Command::Resize {piece, from, to});
+				},
+				_ => {}
+			}
+		}
+	}
+
+	pub fn draw(&mut self) {
+		if let Some(world) = &mut self.playtest {
+			world.draw();
+			draw_text("Playtest - Escape to edit, R to restart", 10., screen_height() - 10., 24., WHITE);
+			return;
+		}
+
+		set_camera(&self.camera);
+
+		// Grid
+		let unit = self.blueprint.unit;
+		let top_left = self.camera.screen_to_world(Vec2::ZERO);
+		let bottom_right = self.camera.screen_to_world(vec2(screen_width(), screen_height()));
+		let first = (top_left / unit).floor();
+		let last = (bottom_right / unit).ceil();
+		for x in (first.x as i32)..=(last.x as i32) {
+			draw_line(x as f32 * unit.x, top_left.y, x as f32 * unit.x, bottom_right.y, 1., DARKGRAY);
+		}
+		for y in (first.y as i32)..=(last.y as i32) {
+			draw_line(top_left.x, y as f32 * unit.y, bottom_right.x, y as f32 * unit.y, 1., DARKGRAY);
+		}
+
+		for p in self.blueprint.pieces.iter() {
+			Self::draw_piece(p);
+			draw_rectangle(
+				p.position.x + p.size.x - Self::HANDLE,
+				p.position.y + p.size.y - Self::HANDLE,
+				Self::HANDLE,
+				Self::HANDLE,
+				ORANGE
+			);
+		}
+
+		set_default_camera();
+
+		draw_text(
+			&format!(
+				"{} - Tool: {} [1-0, Tab]  Unit: {} [[ ]]  {}",
+				self.level,
+				Self::TOOLS[self.tool],
+				self.blueprint.unit.x,
+				self.message
+			),
+			10.,
+			screen_height() - 30.,
+			20.,
+			WHITE
+		);
+		draw_text(
+			"1-0/Tab: tool, Left: place/move, drag corner: resize, Right: delete, C: change kind, Middle/arrows: pan, Wheel: zoom, Ctrl+Z/Y: undo/redo, Ctrl+S: save, P: playtest",
+			10.,
+			screen_height() - 10.,
+			14.,
+			GRAY
+		);
+	}
+
+	fn draw_piece(p: &Piece) {
+		match &p.keyword[..] {
+			"Spike" => draw_triangle(
+				p.position + vec2(p.size.x / 2., 0.),
+				p.position + vec2(0., p.size.y),
+				p.position + p.size,
+				WHITE
+			),
+			"Player" => draw_rectangle(p.position.x, p.position.y, p.size.x, p.size.y, BLUE),
+			"Goal" => draw_rectangle(p.position.x, p.position.y, p.size.x, p.size.y, GREEN),
+			"Checkpoint" => draw_rectangle(p.position.x, p.position.y, p.size.x, p.size.y, SKYBLUE),
+			"JumpPad" => draw_rectangle(p.position.x, p.position.y + p.size.y * 0.7, p.size.x, p.size.y * 0.3, YELLOW),
+			"SlopeUp" | "SlopeDown" => draw_triangle(
+				p.position + vec2(0., p.size.y),
+				p.position + p.size,
+				p.position + if p.keyword == "SlopeUp" { vec2(p.size.x, 0.) } else { Vec2::ZERO },
+				WHITE
+			),
+			"OneWayPlatform" => draw_rectangle(p.position.x, p.position.y, p.size.x, p.size.y * 0.25, WHITE),
+			"GravityPortal" => draw_rectangle_lines(p.position.x, p.position.y, p.size.x, p.size.y, 4., PURPLE),
+			"SpeedPortal" => draw_rectangle_lines(p.position.x, p.position.y, p.size.x, p.size.y, 4., ORANGE),
+			"Platform" => {
+				draw_rectangle(p.position.x, p.position.y, p.size.x, p.size.y, GRAY);
+				// Path followed by the platform
+				if let Some(route) = &p.route {
+					let mut from = p.position;
+					for w in route.waypoints.iter() {
+						draw_line(from.x, from.y, p.position.x + w.x, p.position.y + w.y, 2., GRAY);
+						from = p.position + *w;
+					}
+					if route.looping {
+						draw_line(from.x, from.y, p.position.x, p.position.y, 2., GRAY);
+					}
+				}
+			},
+			_ => draw_rectangle(p.position.x, p.position.y, p.size.x, p.size.y, WHITE)
+		}
+	}
+
+	/// Top left corner of the grid cell containing `v`
+	fn snap(&self, v: Vec2) -> Vec2 {
+		(v / self.blueprint.unit).floor() * self.blueprint.unit
+	}
+
+	fn on_handle(&self, piece: usize, v: Vec2) -> bool {
+		let p = &self.blueprint.pieces[piece];
+		let corner = p.position + p.size;
+		corner.x - v.x <= Self::HANDLE && corner.y - v.y <= Self::HANDLE
+	}
+
+	/// Starts playing the level being edited with the player at `from`
+	async fn playtest(&mut self, from: Vec2, rm: &mut ResourceManager) {
+		if self.players() > 1 {
+			self.message = "Cannot playtest: more than one player".to_string();
+			return;
+		}
+		self.playtest_from = from;
+		let mut blueprint = self.blueprint.clone();
+		match blueprint.pieces.iter_mut().find(|p| p.keyword == "Player") {
+			Some(player) => player.position = from,
+			None => {
+				let mut player = Piece::new("Player", from, blueprint.unit);
+				player.speed.x = 5.;
+				blueprint.pieces.insert(0, player);
+			}
+		}
+
+		match World::from_source(&self.level, &blueprint.to_string(), rm).await {
+			Ok(world) => self.playtest = Some(world),
+			Err(e) => self.message = e.to_string()
+		}
+	}
+
+	/// Saves the level, or a copy of it if saving would lose part of its
+	/// source, see `Editor::flattens()`
+	fn save(&mut self) {
+		if self.players() > 1 {
+			self.message = "Cannot save: more than one player".to_string();
+			return;
+		}
+
+		let original = self.level.clone();
+		if self.flattened {
+			let stem = original.strip_suffix(".lvl").unwrap_or(&original);
+			self.level = format!("{stem}-edited.lvl");
+		}
+
+		self.message = match std::fs::write(format!("{}/{}", World::levels_dir(), self.level), self.blueprint.to_string()) {
+			Ok(()) if self.flattened => {
+				self.flattened = false;
+				format!("Saved as {}, {original} has includes, prefabs, loops, constants or comments", self.level)
+			},
+			Ok(()) => "Saved".to_string(),
+			Err(e) => {
+				self.level = original;
+				format!("Cannot save: {e}")
+			}
+		};
+	}
+}
@@ -0,0 +1,104 @@
+//! Reversible edits of a blueprint
+
+use macroquad::prelude::*;
+
+use crate::game::world::blueprint::*;
+
+/// Single edit, holding what is needed to apply and revert it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+	Add(usize, Piece),
+	Remove(usize, Piece),
+	Move {piece: usize, from: Vec2, to: Vec2},
+	Resize {piece: usize, from: Vec2, to: Vec2},
+	ChangeKind {piece: usize, from: String, to: String},
+	ChangeUnit {from: Vec2, to: Vec2}
+}
+
+impl Command {
+	pub fn apply(&self, blueprint: &mut Blueprint) {
+		match self {
+			Command::Add(i, piece) => blueprint.pieces.insert(*i, piece.clone()),
+			Command::Remove(i, _) => { blueprint.pieces.remove(*i); },
+			Command::Move {piece, to, ..} => blueprint.pieces[*piece].position = *to,
+			Command::Resize {piece, to, ..} => blueprint.pieces[*piece].size = *to,
+			Command::ChangeKind {piece, to, ..} => blueprint.pieces[*piece].keyword = to.clone(),
+			Command::ChangeUnit {to, ..} => blueprint.unit = *to
+		}
+	}
+
+	pub fn revert(&self, blueprint: &mut Blueprint) {
+		match self {
+			Command::Add(i, _) => { blueprint.pieces.remove(*i); },
+			Command::Remove(i, piece) => blueprint.pieces.insert(*i, piece.clone()),
+			Command::Move {piece, from, ..} => blueprint.pieces[*piece].position = *from,
+			Command::Resize {piece, from, ..} => blueprint.pieces[*piece].size = *from,
+			Command::ChangeKind {piece, from, ..} => blueprint.pieces[*piece].keyword = from.clone(),
+			Command::ChangeUnit {from, ..} => blueprint.unit = *from
+		}
+	}
+
+	/// Whether applying the command changes nothing, e.g. a click without drag
+	pub fn is_noop(&self) -> bool {
+		match self {
+			Command::Add(..) | Command::Remove(..) => false,
+			Command::Move {from, to, ..} | Command::Resize {from, to, ..} | Command::ChangeUnit {from, to} => from == to,
+			Command::ChangeKind {from, to, ..} => from == to
+		}
+	}
+}
+
+/// Unbounded undo and redo stacks
+#[derive(Debug, Default)]
+pub struct History {
+	done: Vec::<Command>,
+	undone: Vec::<Command>
+}
+
+impl History {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Applies `command` and records it
+	pub fn execute(&mut self, command: Command, blueprint: &mut Blueprint) {
+		command.apply(blueprint);
+		self.record(command);
+	}
+
+	/// Records a command which was already applied, e.g. at the end of a drag
+	pub fn record(&mut self, command: Command) {
+		if !command.is_noop() {
+			self.done.push(command);
+			self.undone.clear();
+		}
+	}
+
+	/// Reverts the last command, returns false if there is none
+	pub fn undo(&mut self, blueprint: &mut Blueprint) -> bool {
+		match self.done.pop() {
+			Some(command) => {
+				command.revert(blueprint);
+				self.undone.push(command);
+				true
+			},
+			None => false
+		}
+	}
+
+	/// Applies the last undone command again, returns false if there is none
+	pub fn redo(&mut self, blueprint: &mut Blueprint) -> bool {
+		match self.undone.pop() {
+			Some(command) => {
+				command.apply(blueprint);
+				self.done.push(command);
+				true
+			},
+			None => false
+		}
+	}
+
+	pub fn can_undo(&self) -> bool { !self.done.is_empty() }
+
+	pub fn can_redo(&self) -> bool { !self.undone.is_empty() }
+}
@@ -1,50 +1,238 @@
 //! In-game world
 
-use world::World;
+use world::{World, LevelLoadError, RunStatus, Start};
+use std::rc::Rc;
+
 use macroquad::prelude::*;
 use resource_manager::*;
+use input::*;
+use replay::*;
 
 pub mod world;
 pub mod resource_manager;
 pub mod animation;
+pub mod input;
+pub mod headless;
+pub mod replay;
+pub mod statistics;
+
+pub mod object;
 
-mod object;
+/// How a run ended
+#[derive(Debug, Clone, Copy)]
+pub struct RunReport {
+	pub status: RunStatus,
+	/// Time spent playing, in seconds
+	pub time: f32,
+	pub position: Vec2
+}
 
 /// Manages the world to application behaviour
 pub struct Game {
 	world: World,
+	input: Recorder,
+	/// Best recorded run of the level, raced against
+	ghost: Option<(World, ScriptedInput)>,
 	paused: bool,
-	loaded: String
+	loaded: String,
+	watching: bool,
+	respawned: bool,
+	saved: bool,
+	reported: bool,
+	/// Where the player starts instead of the level's beginning, kept on reload
+	start: Option<Start>,
+	/// Looped while the game exists
+	music: Option<Rc<Resource>>
+}
+
+impl Drop for Game {
+	fn drop(&mut self) {
+		if let Some(music) = &self.music {
+			music.stop_if_sound();
+		}
+	}
 }
 
 impl Game {
-	pub async fn new(to_load: &str, rm: &mut ResourceManager) -> Self {
-		Self {
-			world: World::from((to_load, rm)).await,
+	/// Starts playing a level, the run is saved as a replay if completed
+	pub async fn new(to_load: &str, rm: &mut ResourceManager) -> Result<Self, LevelLoadError> {
+		Self::start(to_load, false, rm).await
+	}
+
+	/// Starts watching the replay of a level
+	pub async fn watch(to_load: &str, rm: &mut ResourceManager) -> Result<Self, LevelLoadError> {
+		Self::start(to_load, true, rm).await
+	}
+
+	async fn start(to_load: &str, watching: bool, rm: &mut ResourceManager) -> Result<Self, LevelLoadError> {
+		let world = World::from((to_load, &mut *rm)).await?;
+
+		let input: Box::<dyn InputSource> = if watching {
+			let replay = Replay::load(World::levels_dir(), to_load, world.hash())
+				.map_err(|e| LevelLoadError::Replay(to_load.to_owned(), e))?;
+			Box::new(Playback::from(&replay))
+		} else {
+			Box::new(Keyboard)
+		};
+
+		let ghost = if watching {
+			None
+		} else {
+			Replay::load(World::levels_dir(), to_load, world.hash())
+				.ok()
+				.map(|best| (world.ghost(), best.input()))
+		};
+
+		let music = match &world.metadata().music {
+			Some(music) => match rm.request(&format!("res/sounds/{music}")).await {
+				Ok(music) => {
+					music.play_if_sound(true);
+					Some(music)
+				},
+				Err(e) => {
+					eprintln!("Game: Cannot play music '{music}': {e:?}");
+					None
+				}
+			},
+			None => None
+		};
+
+		Ok(Self {
+			world,
+			input: Recorder::new(input),
+			ghost,
 			paused: false,
-			loaded: to_load.to_owned()
-		}
+			loaded: to_load.to_owned(),
+			watching,
+			respawned: false,
+			saved: false,
+			reported: false,
+			start: None,
+			music
+		})
+	}
+
+	/// Moves the player to `start`, now and on every reload.
+	/// Runs not starting at the beginning are not saved as replays.
+	pub fn start_at(&mut self, start: Start) -> Result<(), LevelLoadError> {
+		self.world.start_at(&self.loaded, start)?;
+		self.start = Some(start);
+		Ok(())
 	}
 
 	pub fn is_finished(&self) -> bool { !self.world.is_playing() }
 
-	pub async fn reload(&mut self, rm: &mut ResourceManager) {
+	/// Whether a replay is being watched
+	pub fn is_watching(&self) -> bool { self.watching }
+
+	/// Name of the level being played
+	pub fn level(&self) -> &str { &self.loaded }
+
+	pub fn world(&self) -> &World { &self.world }
+
+	/// World replaying the best run of the level, if any
+	pub fn ghost(&self) -> Option<&World> { self.ghost.as_ref().map(|(ghost, _)| ghost) }
+
+	/// Time spent playing, in seconds
+	pub fn elapsed(&self) -> f32 { self.world.ticks() as f32 * World::TICK }
+
+	/// Outcome of the run, given once when it ends. Replays and runs not
+	/// starting at the beginning are not reported.
+	pub fn take_report(&mut self) -> Option<RunReport> {
+		if !self.is_finished() || self.watching || self.start.is_some() || self.reported {
+			return None;
+		}
+
+		self.reported = true;
+		Some(RunReport {
+			status: self.world.status(),
+			time: self.elapsed(),
+			position: self.world.player().map(|p| p.position).unwrap_or_default()
+		})
+	}
+
+	/// Whether the run ended by reaching a goal
+	pub fn is_complete(&self) -> bool { self.world.status() == RunStatus::Complete }
+
+	pub fn has_checkpoint(&self) -> bool { self.world.has_checkpoint() }
+
+	pub fn background(&self) -> Color { self.world.metadata().background }
+
+	/// Brings the player back to the last checkpoint, see World::respawn()
+	/// Runs using a checkpoint are not saved as replays.
+	pub fn respawn(&mut self) -> bool {
+		let r = self.world.respawn();
+		self.respawned |= r;
+		self.reported &= !r;
+		r
+	}
+
+	pub async fn reload(&mut self, rm: &mut ResourceManager) -> Result<(), LevelLoadError> {
 		self.world.clear();
-		*self = Self::new(&self.loaded, rm).await;
+		// Stopped before the new game plays it again
+		if let Some(music) = self.music.take() {
+			music.stop_if_sound();
+		}
+		let start = self.start;
+		*self = Self::start(&self.loaded, self.watching, rm).await?;
+		if let Some(start) = start {
+			self.start_at(start)?;
+		}
+		Ok(())
+	}
+
+	/// Whether the player asked for a restart during this frame
+	pub fn restart_requested(&mut self) -> bool { self.input.restart_requested() }
+
+	/// Drives the game with `input` instead of the keyboard
+	pub fn input(mut self, input: impl InputSource + 'static) -> Self {
+		self.input = Recorder::new(Box::new(input));
+		self
 	}
 
 	pub fn update(&mut self) {
+		self.advance(get_frame_time());
+	}
+
+	/// Runs the game for `dt` seconds, see World::update()
+	pub fn advance(&mut self, dt: f32) {
 		if !self.paused {
-			self.world.update();
+			self.world.update(dt, &mut self.input);
+			if let Some((ghost, input)) = &mut self.ghost {
+				ghost.update(dt, input);
+			}
 		}
 
-		if is_key_pressed(KeyCode::Escape) {
+		// Saving completed runs, if faster than the best one
+		if self.is_complete() && !self.watching && !self.respawned && self.start.is_none() && !self.saved {
+			self.saved = true;
+			let replay = Replay::new(&self.loaded, self.world.hash(), self.input.ticks().to_vec());
+			let best = Replay::load(World::levels_dir(), &self.loaded, self.world.hash()).ok();
+
+			if best.is_none_or(|best| replay.len() < best.len()) {
+				if let Err(e) = replay.save(World::levels_dir()) {
+					eprintln!("Game: Cannot save replay of '{}': {e}", self.loaded);
+				}
+			}
+		}
+
+		if self.input.pause_requested() {
 			self.paused = !self.paused;
 		}
 	}
 
 	pub fn draw(&mut self) {
 		self.world.draw();
+		if let Some((ghost, _)) = &mut self.ghost {
+			ghost.draw_players(&self.world);
+		}
+		if self.watching {
+			draw_text("Replay", screen_width() - 110., 40., 40., WHITE);
+		}
+
+		let timer = format!("{:.2}", self.elapsed());
+		let timer_width = measure_text(&timer, None, 40, 1.).width;
+		draw_text(&timer, (screen_width() - timer_width) / 2., 40., 40., WHITE);
 		if self.paused {
 			draw_rectangle(
 				0.,
@@ -34,6 +34,19 @@ impl Animation {
 		}
 	}
 
+	/// Animation of a single frame drawn from no spritesheet
+	pub fn null() -> Self {
+		Self {
+			spritesheet: Rc::new(Resource::Null),
+			id: 0,
+			length: 1,
+			sustain: 0,
+			looped: false,
+			current_frame: 0u32,
+			sustain_countdown: 1u32
+		}
+	}
+
 	pub fn rewind(&mut self) { self.current_frame = 0; }
 
 	pub fn update(&mut self) {
@@ -52,14 +65,14 @@ impl Animation {
 		}
 	}
 
-	pub fn draw(&self, pos: Vec2, size: Vec2, rotation: f32) {
+	pub fn draw(&self, pos: Vec2, size: Vec2, rotation: f32, color: Color) {
 		
 		// Drawing texture
 		if let Resource::Texture(texture) = self.spritesheet.as_ref() {
 			draw_texture_ex(
 				texture,
 				pos.x, pos.y,
-				WHITE,
+				color,
 				DrawTextureParams {
 					dest_size: Some(vec2(size.x, size.y)),
 					source: Some(Rect {
@@ -0,0 +1,89 @@
+//! Windowless simulation of levels, for tests and tools
+
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+
+use macroquad::prelude::*;
+
+use super::world::{World, LevelLoadError, RunStatus};
+use super::resource_manager::ResourceManager;
+use super::input::*;
+
+/// Runs a future to completion on the current thread.
+/// Only meant for futures that never wait on anything, such as level
+/// loading with a null resource manager.
+pub fn block_on<F: Future>(f: F) -> F::Output {
+	let mut f = std::pin::pin!(f);
+	let mut context = Context::from_waker(Waker::noop());
+	loop {
+		if let Poll::Ready(r) = f.as_mut().poll(&mut context) {
+			return r;
+		}
+	}
+}
+
+/// A level stepped tick by tick with scripted input, without window,
+/// textures nor sounds
+/// # Example
+/// ```
+/// # use runner::game::headless::Simulation;
+/// # use runner::game::world::RunStatus;
+/// let mut simulation = Simulation::load("little.lvl").unwrap();
+/// simulation.step(10);
+/// assert_eq!(simulation.status(), RunStatus::Playing);
+/// ```
+pub struct Simulation {
+	world: World,
+	input: Box::<dyn InputSource>
+}
+
+impl Simulation {
+	/// Loads a level from the levels directory
+	pub fn load(level: &str) -> Result<Self, LevelLoadError> {
+		Ok(Self::new(block_on(World::from((level, &mut ResourceManager::null())))?))
+	}
+
+	/// Loads a level from its source code
+	pub fn from_source(level: &str, source: &str) -> Result<Self, LevelLoadError> {
+		Ok(Self::new(block_on(World::from_source(level, source, &mut ResourceManager::null()))?))
+	}
+
+	fn new(world: World) -> Self {
+		Self {
+			world,
+			input: Box::new(ScriptedInput::new())
+		}
+	}
+
+	/// Drives the simulation with `input` from now on
+	pub fn input(mut self, input: impl InputSource + 'static) -> Self {
+		self.input = Box::new(input);
+		self
+	}
+
+	/// Runs `ticks` simulation ticks
+	pub fn step(&mut self, ticks: usize) {
+		for _ in 0..ticks {
+			self.world.step(self.input.poll());
+		}
+	}
+
+	/// Runs until the run is over, at most `max_ticks` ticks.
+	/// Returns the number of ticks run.
+	pub fn run(&mut self, max_ticks: usize) -> usize {
+		let mut ticks = 0;
+		while ticks < max_ticks && self.world.is_playing() {
+			self.step(1);
+			ticks += 1;
+		}
+		ticks
+	}
+
+	pub fn status(&self) -> RunStatus { self.world.status() }
+
+	pub fn world(&self) -> &World { &self.world }
+
+	pub fn player_position(&self) -> Option<Vec2> {
+		self.world.player().map(|p| p.position)
+	}
+}
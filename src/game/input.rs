@@ -0,0 +1,80 @@
+//! Player controls, decoupled from any device
+
+use macroquad::prelude::*;
+
+/// Controls sampled for one simulation tick
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InputState {
+	pub jump: bool
+}
+
+/// Anything able to drive the simulation: keyboard, bots, replays...
+pub trait InputSource {
+	/// Samples the controls for the next simulation tick
+	fn poll(&mut self) -> InputState;
+
+	/// Whether pausing was requested during the current frame
+	fn pause_requested(&mut self) -> bool { false }
+
+	/// Whether restarting was requested during the current frame
+	fn restart_requested(&mut self) -> bool { false }
+}
+
+/// Live keyboard controls
+pub struct Keyboard;
+
+impl InputSource for Keyboard {
+	fn poll(&mut self) -> InputState {
+		InputState {
+			jump: is_key_down(KeyCode::Space)
+		}
+	}
+
+	fn pause_requested(&mut self) -> bool { is_key_pressed(KeyCode::Escape) }
+
+	fn restart_requested(&mut self) -> bool { is_key_pressed(KeyCode::R) }
+}
+
+/// Plays a fixed sequence of inputs, one per tick.
+/// Once the sequence is exhausted, no control is pressed.
+/// # Example
+/// ```
+/// # use runner::game::input::*;
+/// // Waits half a second then holds jump for one tick
+/// let input = ScriptedInput::new()
+///     .then(InputState::default(), 30)
+///     .then(InputState { jump: true }, 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedInput {
+	ticks: Vec::<InputState>,
+	cursor: usize
+}
+
+impl ScriptedInput {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	/// Appends `n` ticks of `state` to the script
+	pub fn then(mut self, state: InputState, n: usize) -> Self {
+		self.ticks.extend(std::iter::repeat_n(state, n));
+		self
+	}
+
+	pub fn is_finished(&self) -> bool { self.cursor >= self.ticks.len() }
+}
+
+impl From<Vec::<InputState>> for ScriptedInput {
+	fn from(ticks: Vec::<InputState>) -> Self {
+		Self { ticks, cursor: 0 }
+	}
+}
+
+impl InputSource for ScriptedInput {
+	fn poll(&mut self) -> InputState {
+		let r = self.ticks.get(self.cursor).copied().unwrap_or_default();
+		self.cursor += 1;
+		r
+	}
+}
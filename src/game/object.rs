@@ -4,9 +4,11 @@ use macroquad::prelude::*;
 use macroquad::audio::*;
 
 use std::rc::Rc;
+use std::fmt;
 
 use super::animation::*;
 use super::resource_manager::*;
+use super::input::InputState;
 
 #[derive(Debug, Clone)]
 pub enum PlayerState {
@@ -28,24 +30,148 @@ impl Action {
 			sound
 		}
 	}
+
+	/// Action without animation nor sound
+	fn null() -> Self {
+		Self::new(Animation::null(), Rc::new(Resource::Null))
+	}
+}
+
+/// Waypoints followed by a moving platform, back and forth unless looping
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+	/// Absolute positions, the first one being where the platform starts
+	pub waypoints: Vec::<Vec2>,
+	/// Distance covered every tick, in pixels. Level files give it as `at Npx`,
+	/// or as `at N` in horizontal units.
+	pub speed: f32,
+	/// Whether the platform goes from the last waypoint straight back to the first
+	pub looping: bool,
+	/// Index of the waypoint being headed to
+	target: usize,
+	backwards: bool
+}
+
+impl Path {
+	/// Moves along the path from `position` for one tick, returns the motion
+	fn advance(&mut self, position: Vec2) -> Vec2 {
+		if self.waypoints.len() < 2 {
+			return Vec2::ZERO;
+		}
+
+		let mut current = position;
+		let mut left = self.speed;
+		// Bounded as waypoints may all be at the same place
+		for _ in 0..=self.waypoints.len() {
+			let target = self.waypoints[self.target];
+			let distance = current.distance(target);
+			if distance > left {
+				current += (target - current) / distance * left;
+				break;
+			}
+			current = target;
+			left -= distance;
+			self.next_target();
+		}
+		current - position
+	}
+
+	fn next_target(&mut self) {
+		let last = self.waypoints.len() - 1;
+		if self.looping {
+			self.target = if self.target == last { 0 } else { self.target + 1 };
+		} else if self.backwards && self.target == 0 {
+			self.backwards = false;
+			self.target = 1;
+		} else if self.backwards {
+			self.target -= 1;
+		} else if self.target == last {
+			self.backwards = true;
+			self.target = last - 1;
+		} else {
+			self.target += 1;
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
 pub enum ObjectKind {
 	Player {state: PlayerState, run: Action, jump: Action, die: Action},
 	Wall,
-	Spike
+	Spike,
+	Goal,
+	Checkpoint,
+	/// Wall carrying the player along its path
+	Platform (Path),
+	/// Launches the player upwards, relatively to its gravity
+	JumpPad,
+	/// Flips the gravity of the player, who then lands on ceilings
+	GravityPortal,
+	/// Solid only when landed on, passed through from below and from the sides
+	OneWayPlatform,
+	/// Right triangle filling the bottom of the object, rising to the right
+	/// or to the left
+	Slope {rising: bool},
+	/// Sets the horizontal speed of the player, in pixels per tick
+	SpeedPortal (f32)
 }
 
 impl ObjectKind {
+	/// Keyword of every kind of object, see ObjectKind::keyword()
+	pub const KEYWORDS: [&'static str; 12] = [
+		"Player", "Wall", "Spike", "Goal", "Checkpoint", "Platform", "JumpPad",
+		"GravityPortal", "OneWayPlatform", "SlopeUp", "SlopeDown", "SpeedPortal"
+	];
+
 	pub async fn from(arg: (&str, &mut ResourceManager)) -> Self {
 		match arg.0 {
-			"Spike" => Self::Spike,
 			"Player" => Self::player(arg.1).await,
+			keyword => Self::placeholder(keyword)
+		}
+	}
+
+	/// Kind declared by `keyword`, without loading any resource: players
+	/// are neither animated nor heard
+	pub fn placeholder(keyword: &str) -> Self {
+		match keyword {
+			"Player" => Self::Player {
+				state: PlayerState::Jumping,
+				run: Action::null(),
+				jump: Action::null(),
+				die: Action::null()
+			},
+			"Spike" => Self::Spike,
+			"Goal" => Self::Goal,
+			"Checkpoint" => Self::Checkpoint,
+			"Platform" => Self::Platform(Path::default()),
+			"JumpPad" => Self::JumpPad,
+			"GravityPortal" => Self::GravityPortal,
+			"OneWayPlatform" => Self::OneWayPlatform,
+			"SlopeUp" => Self::Slope {rising: true},
+			"SlopeDown" => Self::Slope {rising: false},
+			"SpeedPortal" => Self::SpeedPortal(Object::PORTAL_SPEED),
 			_ => Self::Wall
 		}
 	}
 
+	/// Keyword used to declare this kind of object in level files
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			Self::Player {..} => "Player",
+			Self::Wall => "Wall",
+			Self::Spike => "Spike",
+			Self::Goal => "Goal",
+			Self::Checkpoint => "Checkpoint",
+			Self::Platform(_) => "Platform",
+			Self::JumpPad => "JumpPad",
+			Self::GravityPortal => "GravityPortal",
+			Self::OneWayPlatform => "OneWayPlatform",
+			Self::Slope {rising: true} => "SlopeUp",
+			Self::Slope {rising: false} => "SlopeDown",
+			Self::SpeedPortal(_) => "SpeedPortal"
+		}
+	}
+
 	async fn player(rm: &mut ResourceManager) -> Self {
 		
 		let run_sound = rm.request("res/sounds/running.wav").await.unwrap();
@@ -69,21 +195,51 @@ impl ObjectKind {
 	}
 }
 
+impl fmt::Display for ObjectKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.keyword())
+	}
+}
+
+
+/// Player state saved when reaching a checkpoint
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+	position: Vec2,
+	speed: Vec2,
+	gravity: f32,
+	state: PlayerState
+}
 
 /// Represents any object in-game
 #[derive(Debug, Clone)]
 pub struct Object {
 	pub position: Vec2,
+	/// Position at the beginning of the current tick, used for rendering
+	previous_position: Vec2,
 	pub size: Vec2,
 	speed: Vec2,
+	/// Added to the vertical speed of players every tick
+	gravity: f32,
 
 	pub kind: ObjectKind,
 	is_on_ground: bool,
+	/// Vertical over horizontal motion along the ground, non zero on slopes
+	ground_slope: f32,
 	alive: bool,
+	finished: bool,
+	checkpoint_reached: bool,
+	/// Whether a portal is touched during this tick and the previous one,
+	/// portals only act when entered
+	in_portal: bool,
+	was_in_portal: bool,
 
 	rotation: f32,
 
-	trail: Vec::<Vec2>
+	trail: Vec::<Vec2>,
+
+	/// Ghosts make no sound and are drawn translucent
+	ghost: bool
 }
 
 impl From<ObjectKind> for Object {
@@ -92,8 +248,17 @@ impl From<ObjectKind> for Object {
 	}	
 }
 
+impl Default for Object {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl Drop for Object {
 	fn drop(&mut self) {
+		if self.ghost {
+			return;
+		}
 		if let ObjectKind::Player {run, ..} = &self.kind {
 			if let Resource::Sound {sound, ..} = run.sound.as_ref() {
 				stop_sound(&sound);
@@ -104,30 +269,60 @@ impl Drop for Object {
 
 impl Object {
 	const TRAIL_LENGTH: usize = 30;
+	const GHOST_OPACITY: f32 = 0.4;
+
+	/// Gravity of objects unless the level says otherwise, in pixels per tick²
+	pub const GRAVITY: f32 = 1.;
+
+	/// Speed given by speed portals unless the level says otherwise, in pixels per tick
+	pub const PORTAL_SPEED: f32 = 10.;
+
+	/// Height reached thanks to jump pads, in player heights
+	const JUMP_PAD_HEIGHT: f32 = 4.;
+
+	/// Distance to a slope under which players stick to it, in pixels
+	const SLOPE_TOLERANCE: f32 = 0.01;
 
 	/// Default constructor
 	/// # Example
 	/// ```
+	/// # use macroquad::prelude::*;
+	/// # use runner::game::object::*;
 	/// let o = Object::new()
-	///		.position(Vec2::new(100., 100.))
-	///		.size(Vec2::new(50., 50.))
-	///		.kind(ObjectKind::Wall);
+	///     .position(Vec2::new(100., 100.))
+	///     .size(Vec2::new(50., 50.))
+	///     .kind(ObjectKind::Wall);
 	/// ```
 	pub fn new() -> Self {
 		Self {
 			position: Vec2::ZERO,
+			previous_position: Vec2::ZERO,
 			size: Vec2::ONE * 30.,
 			speed: Vec2::ZERO,
+			gravity: Self::GRAVITY,
 			kind: ObjectKind::Wall,
 			is_on_ground: false,
+			ground_slope: 0.,
 			alive: true,
+			finished: false,
+			checkpoint_reached: false,
+			in_portal: false,
+			was_in_portal: false,
 			rotation: 0.,
-			trail: Default::default()
+			trail: Default::default(),
+			ghost: false
 		}
 	}
 
+	/// Also moves the start of the path of platforms
 	pub fn position(mut self, x: Vec2) -> Self {
 		self.position = x;
+		self.previous_position = x;
+		if let ObjectKind::Platform(path) = &mut self.kind {
+			if let Some(start) = path.waypoints.first_mut() {
+				*start = x;
+			}
+		}
 		self
 	}
 
@@ -141,20 +336,97 @@ impl Object {
 		self
 	}
 
+	pub fn gravity(mut self, x: f32) -> Self {
+		self.gravity = x;
+		self
+	}
+
 	pub fn kind(mut self, x: ObjectKind) -> Self {
 		self.kind = x;
 		self
 	}
 
+	pub fn ghost(mut self, x: bool) -> Self {
+		self.ghost = x;
+		self
+	}
+
+	pub fn get_speed(&self) -> Vec2 { self.speed }
+
+	pub fn get_gravity(&self) -> f32 { self.gravity }
+
 	pub fn is_alive(&self) -> bool { self.alive }
 
-	/// Update function, has to be called once per game-loop
+	/// Whether this object reached a goal
+	pub fn is_finished(&self) -> bool { self.finished }
+
+	/// Whether a checkpoint was touched since last call
+	pub fn take_checkpoint(&mut self) -> bool {
+		std::mem::replace(&mut self.checkpoint_reached, false)
+	}
+
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			position: self.position,
+			speed: self.speed,
+			gravity: self.gravity,
+			state: if let ObjectKind::Player {state, ..} = &self.kind {
+				state.clone()
+			} else {
+				PlayerState::Running
+			}
+		}
+	}
+
+	/// Moves the object without interpolating from its previous position
+	pub fn teleport(&mut self, position: Vec2) {
+		self.position = position;
+		self.previous_position = position;
+	}
+
+	/// Brings a player back to life as it was when `s` was taken
+	pub fn restore(&mut self, s: &Snapshot) {
+		self.position = s.position;
+		self.previous_position = s.position;
+		self.speed = s.speed;
+		self.gravity = s.gravity;
+		self.rotation = 0.;
+		self.in_portal = false;
+		self.was_in_portal = false;
+		self.is_on_ground = false;
+		self.trail.clear();
+
+		if let ObjectKind::Player {state, run, die, ..} = &mut self.kind {
+			*state = s.state.clone();
+			die.animation.rewind();
+			if !self.alive && !self.ghost {
+				run.sound.play_if_sound(true);
+			}
+		}
+
+		self.alive = true;
+		self.finished = false;
+	}
+
+	/// Marks the beginning of a simulation tick, see Object::draw().
+	/// Platforms know their motion for the tick from then on, so that
+	/// players colliding them before their update can ride them.
+	pub fn begin_tick(&mut self) {
+		self.previous_position = self.position;
+		if let ObjectKind::Platform(path) = &mut self.kind {
+			self.speed = path.advance(self.position);
+		}
+	}
+
+	/// Update function, has to be called once per simulation tick
 	/// Abstracts physics but not collisions, see Object::collide().
-	pub fn update(&mut self) {
+	pub fn update(&mut self, input: &InputState) {
 		// Trail management
-		self.trail.push(self.position + self.size * 0.5);
-		if self.trail.len() > Self::TRAIL_LENGTH {
-			self.trail.remove(0);
+		if !matches!(self.kind, ObjectKind::Platform(_)) {
+			self.trail.push(self.position + self.size * 0.5);
+			if self.trail.len() > Self::TRAIL_LENGTH {
+				self.trail.remove(0);
+			}
 		}
 
 		self.position += self.speed;
@@ -167,18 +439,23 @@ impl Object {
 			}
 
 			if !self.alive {
-				self.speed.y += 1.;
+				self.speed.y += self.gravity;
 				self.speed.x *= 0.95;
 				*state = PlayerState::Dying;
 			} else {
 				// Inputs
-				self.speed.y += 1.;
-				if is_key_down(KeyCode::Space) && self.is_on_ground {
-					let f = self.position + Vec2::new(self.size.x * 2., -self.size.y);
+				self.speed.y += self.gravity;
+				if input.jump && self.is_on_ground {
+					// Jumping towards the ceiling when gravity is flipped
+					let f = self.position + Vec2::new(self.size.x * 2., -self.size.y * self.gravity.signum());
 					let i = self.position;
-					self.speed.y = self.speed.x * (f.y-i.y)/(f.x-i.x)-(f.x-i.x)/(2.*self.speed.x)-1./2.;
+					self.speed.y = self.speed.x * (f.y-i.y)/(f.x-i.x)-self.gravity*((f.x-i.x)/(2.*self.speed.x)+1./2.);
+					// Keeping the momentum of slopes
+					self.speed.y += self.speed.x * self.ground_slope;
 					
-					jump.sound.play_if_sound(false);
+					if !self.ghost {
+						jump.sound.play_if_sound(false);
+					}
 				}
 				if self.is_on_ground {
 					*state = PlayerState::Running;
@@ -192,11 +469,11 @@ impl Object {
 			if !self.is_on_ground {
 				self.rotation += 0.2;
 			} else {
-				self.rotation = 0.;
+				self.rotation = self.ground_slope.atan();
 			}
 			
 			self.is_on_ground = false;
-		
+			self.was_in_portal = std::mem::replace(&mut self.in_portal, false);
 		}
 	}
 
@@ -206,31 +483,52 @@ impl Object {
 		// colliding objects.
 		if let ObjectKind::Player {..} = self.kind {
 			
-			// Predicting future collision
+			// Predicting future collision, against where moving objects will be
 			let future = self.position + self.speed;
+			let other_future = other.position + other.speed;
 
-			if future.x < other.position.x + other.size.x
-			&& future.x + self.size.x > other.position.x
-			&& future.y < other.position.y + other.size.y
-			&& future.y + self.size.y > other.position.y {
+			if future.x < other_future.x + other.size.x
+			&& future.x + self.size.x > other_future.x
+			&& future.y < other_future.y + other.size.y
+			&& future.y + self.size.y > other_future.y {
 				match other.kind {
-					ObjectKind::Wall => if self.position.y + self.size.y <= other.position.y {
-							self.speed.y *= 0.;
-							// Snapping position to top of object
-							self.position.y = other.position.y - self.size.y;
-
-							self.is_on_ground = true;
-						} else {
+					ObjectKind::Wall | ObjectKind::Platform(_) => if !self.land(other) {
 							if self.position.x + self.size.x <= other.position.x {
 								self.speed.x = 0.;
-								self.position.x = other.position.x - self.size.x;
+								self.position.x = other_future.x - self.size.x;
 							} else if self.position.y >= other.position.y + other.size.y {
 								self.speed.y = 0.;
-								self.position.y = other.position.y + other.size.y;
+								self.position.y = other_future.y + other.size.y;
 							}
 							
 							self.die();
 						},
+					ObjectKind::OneWayPlatform => {
+						self.land(other);
+					},
+					ObjectKind::Slope {rising} => {
+						// Highest point of the ground under the player
+						let corner = |p: Vec2| if rising { p.x + self.size.x } else { p.x };
+						let now = other.surface(corner(self.position));
+						let next = other.surface(corner(future));
+						let bottom = self.position.y + self.size.y;
+
+						let above = bottom <= now + Self::SLOPE_TOLERANCE;
+						// Running down without leaving the ground, unless jumping
+						let standing = above && bottom >= now - Self::SLOPE_TOLERANCE && self.speed.y >= 0.;
+
+						if self.gravity >= 0. && above && (future.y + self.size.y >= next || standing) {
+							self.speed.y = 0.;
+							self.position.y = next - self.size.y;
+							self.is_on_ground = true;
+							self.ground_slope = if rising { -other.size.y / other.size.x } else { other.size.y / other.size.x };
+						} else if self.gravity < 0. && self.land(other) {
+							// Flat bottom used as a ceiling
+						} else if future.y + self.size.y > next {
+							// Hitting the vertical or bottom side
+							self.die();
+						}
+					},
 					ObjectKind::Spike => {
 						// Tests collision more accurately (Spikes are triangles, not squares)
 						let f = Self::new().position(future).size(self.size);
@@ -242,6 +540,23 @@ impl Object {
 							self.die();
 						}
 					},
+					ObjectKind::Goal => self.finish(),
+					ObjectKind::Checkpoint => if self.alive {
+						self.checkpoint_reached = true;
+					},
+					ObjectKind::JumpPad => if self.alive {
+						let height = Self::JUMP_PAD_HEIGHT * self.size.y;
+						self.speed.y = -(2. * self.gravity.abs() * height).sqrt() * self.gravity.signum();
+					},
+					ObjectKind::GravityPortal => if self.alive {
+						if !self.was_in_portal {
+							self.gravity = -self.gravity;
+						}
+						self.in_portal = true;
+					},
+					ObjectKind::SpeedPortal(speed) => if self.alive && !self.finished {
+						self.speed.x = speed;
+					},
 					ObjectKind::Player {..} => todo!()
 				}
 			
@@ -249,7 +564,42 @@ impl Object {
 		}
 	}
 
-	pub fn draw(&mut self) {
+	/// Lands on `other` if coming from the side gravity pulls away from,
+	/// returns false otherwise. Moving objects carry the player.
+	fn land(&mut self, other: &Object) -> bool {
+		let other_future = other.position + other.speed;
+
+		if self.gravity >= 0. && self.position.y + self.size.y <= other.position.y {
+			// Snapping position to top of object
+			self.position.y = other_future.y - self.size.y;
+		} else if self.gravity < 0. && self.position.y >= other.position.y + other.size.y {
+			// Snapping position to bottom of object, as on a ceiling
+			self.position.y = other_future.y + other.size.y;
+		} else {
+			return false;
+		}
+
+		self.speed.y *= 0.;
+		self.position.x += other.speed.x;
+		self.is_on_ground = true;
+		self.ground_slope = 0.;
+		true
+	}
+
+	/// Height of the top of a slope at `x`, clamped to its ends
+	fn surface(&self, x: f32) -> f32 {
+		let t = ((x - self.position.x) / self.size.x).clamp(0., 1.);
+		match self.kind {
+			ObjectKind::Slope {rising: true} => self.position.y + self.size.y * (1. - t),
+			_ => self.position.y + self.size.y * t
+		}
+	}
+
+	/// Draws the object between its previous and current position,
+	/// `alpha` being the progression of the current tick.
+	pub fn draw(&mut self, alpha: f32) {
+		let position = self.previous_position.lerp(self.position, alpha);
+		let tint = if self.ghost { Color::new(1., 1., 1., Self::GHOST_OPACITY) } else { WHITE };
 				
 		// Trail related code
 		for (i, w) in self.trail.windows(2).enumerate() {
@@ -258,7 +608,7 @@ impl Object {
 				w[0].x, w[0].y,
 				w[1].x, w[1].y,
 				trail_factor * self.size.y * 0.3,
-				Color::new(1., 1., 1., trail_factor));
+				Color::new(1., 1., 1., trail_factor * tint.a));
 		}
  
  		// Drawing the object itself
@@ -266,26 +616,113 @@ impl Object {
 		match &mut self.kind {
 			ObjectKind::Player {state, run, jump, die} => {
 				match state {
-					PlayerState::Running => run.animation.draw(self.position, self.size, self.rotation),
-					PlayerState::Jumping => jump.animation.draw(self.position, self.size, self.rotation),
-					PlayerState::Dying => die.animation.draw(self.position, self.size, 0.)
+					PlayerState::Running => run.animation.draw(position, self.size, self.rotation, tint),
+					PlayerState::Jumping => jump.animation.draw(position, self.size, self.rotation, tint),
+					PlayerState::Dying => die.animation.draw(position, self.size, 0., tint)
 				}
 			},
 			ObjectKind::Wall => {
 				draw_rectangle(
-					self.position.x,
-					self.position.y,
+					position.x,
+					position.y,
 					self.size.x,
 					self.size.y,
 					WHITE);
 			},
 			ObjectKind::Spike => {
 				draw_triangle(
-					self.position + Vec2::new(self.size.x/2., 0.0),
-					self.position + Vec2::new(0., self.size.y),
-					self.position + self.size,
+					position + Vec2::new(self.size.x/2., 0.0),
+					position + Vec2::new(0., self.size.y),
+					position + self.size,
+					WHITE
+				);
+			},
+			ObjectKind::Goal => {
+				// Pole
+				draw_rectangle(
+					position.x,
+					position.y,
+					self.size.x * 0.1,
+					self.size.y,
+					WHITE
+				);
+				// Flag
+				draw_triangle(
+					position + Vec2::new(self.size.x * 0.1, 0.),
+					position + Vec2::new(self.size.x * 0.1, self.size.y * 0.5),
+					position + Vec2::new(self.size.x, self.size.y * 0.25),
+					GREEN
+				);
+			},
+			ObjectKind::Checkpoint => {
+				draw_rectangle(
+					position.x,
+					position.y,
+					self.size.x * 0.1,
+					self.size.y,
+					WHITE
+				);
+				draw_rectangle(
+					position.x + self.size.x * 0.1,
+					position.y,
+					self.size.x * 0.5,
+					self.size.y * 0.3,
+					SKYBLUE
+				);
+			},
+			ObjectKind::Platform(_) => {
+				draw_rectangle(
+					position.x,
+					position.y,
+					self.size.x,
+					self.size.y,
+					GRAY
+				);
+				draw_rectangle(
+					position.x,
+					position.y,
+					self.size.x,
+					self.size.y * 0.2,
+					WHITE
+				);
+			},
+			ObjectKind::OneWayPlatform => {
+				// Thin top with dashed supports, as it can be crossed
+				draw_rectangle(
+					position.x,
+					position.y,
+					self.size.x,
+					self.size.y * 0.25,
+					WHITE
+				);
+				let supports = (self.size.x / 10.).floor().max(1.) as usize;
+				for i in 0..supports {
+					let x = position.x + (i as f32 + 0.5) * self.size.x / supports as f32;
+					draw_line(x, position.y, x, position.y + self.size.y, 1., GRAY);
+				}
+			},
+			ObjectKind::Slope {rising} => {
+				let high = if *rising { position + vec2(self.size.x, 0.) } else { position };
+				draw_triangle(
+					position + vec2(0., self.size.y),
+					position + self.size,
+					high,
 					WHITE
 				);
+			},
+			ObjectKind::JumpPad => {
+				draw_rectangle(
+					position.x,
+					position.y + self.size.y * 0.7,
+					self.size.x,
+					self.size.y * 0.3,
+					YELLOW
+				);
+			},
+			ObjectKind::GravityPortal | ObjectKind::SpeedPortal(_) => {
+				let color = if let ObjectKind::GravityPortal = self.kind { PURPLE } else { ORANGE };
+				let center = position + self.size / 2.;
+				draw_ellipse_lines(center.x, center.y, self.size.x / 2., self.size.y / 2., 0., 4., color);
 			}
 		}
 	}
@@ -297,9 +734,27 @@ impl Object {
 		&& self.position.y + self.size.y >= v.y
 	}
 
+	fn finish(&mut self) {
+		if self.alive && !self.finished {
+			self.finished = true;
+			self.speed.x = 0.;
+			if self.ghost {
+				return;
+			}
+			if let ObjectKind::Player {run, ..} = &self.kind {
+				if let Resource::Sound {sound, ..} = run.sound.as_ref() {
+					stop_sound(sound);
+				}
+			}
+		}
+	}
+
 	fn die(&mut self) {
-		if self.alive {
+		if self.alive && !self.finished {
 			self.alive = false;
+			if self.ghost {
+				return;
+			}
 			if let ObjectKind::Player {die, run, ..} = &self.kind {
 				if let Resource::Sound {sound, ..} = run.sound.as_ref() {
 					stop_sound(sound);
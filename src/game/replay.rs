@@ -0,0 +1,225 @@
+//! Input recording and deterministic replays
+//!
+//! A replay file is saved next to its level, `parkour.lvl` giving
+//! `parkour.replay`. It starts with a header then lists inputs run-length
+//! encoded, one `<jump> <ticks>` pair per line:
+//! ```text
+//! Runner replay
+//! level parkour.lvl
+//! hash 8c3f0e2a9b1d4c57
+//! version 0.1.0
+//! 0 20
+//! 1 1
+//! 0 150
+//! ```
+
+use std::fmt;
+use std::fs;
+
+use super::input::*;
+
+#[derive(Debug)]
+pub enum ReplayError {
+	Io (std::io::Error),
+	Malformed (usize),
+	/// The level changed since the replay was recorded
+	LevelMismatch
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "{e}"),
+			Self::Malformed(line) => write!(f, "malformed replay at line {line}"),
+			Self::LevelMismatch => write!(f, "the level changed since this replay was recorded")
+		}
+	}
+}
+
+/// Inputs of a whole run, tick by tick
+#[derive(Debug, Clone)]
+pub struct Replay {
+	level: String,
+	hash: u64,
+	version: String,
+	ticks: Vec::<InputState>
+}
+
+impl Replay {
+	const MAGIC: &'static str = "Runner replay";
+
+	/// `hash` identifies the source code of the level, see Replay::hash()
+	pub fn new(level: &str, hash: u64, ticks: Vec::<InputState>) -> Self {
+		Self {
+			level: level.to_owned(),
+			hash,
+			version: env!("CARGO_PKG_VERSION").to_owned(),
+			ticks
+		}
+	}
+
+	/// Name of the replay file of `level`
+	/// # Example
+	/// ```
+	/// # use runner::game::replay::Replay;
+	/// assert_eq!(Replay::file_name("parkour.lvl"), "parkour.replay");
+	/// ```
+	pub fn file_name(level: &str) -> String {
+		let stem = level.strip_suffix(".lvl").unwrap_or(level);
+		format!("{stem}.replay")
+	}
+
+	/// Name of the level `replay` was recorded on, see Replay::file_name()
+	pub fn level_name(replay: &str) -> String {
+		let stem = replay.strip_suffix(".replay").unwrap_or(replay);
+		format!("{stem}.lvl")
+	}
+
+	/// FNV-1a hash of a level source code followed by the files it includes.
+	/// Stable across platforms and compiler versions, unlike `DefaultHasher`.
+	pub fn hash<'a>(sources: impl IntoIterator<Item = &'a str>) -> u64 {
+		sources.into_iter().fold(0xcbf29ce484222325, |h, source| {
+			// Ending each file with a byte never found in UTF-8
+			source.bytes().chain([0xff]).fold(h, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+		})
+	}
+
+	/// Number of ticks in the replay
+	pub fn len(&self) -> usize { self.ticks.len() }
+
+	pub fn is_empty(&self) -> bool { self.ticks.is_empty() }
+
+	/// Input source playing this replay back
+	pub fn input(&self) -> ScriptedInput {
+		ScriptedInput::from(self.ticks.clone())
+	}
+
+	/// Loads the replay of `level` from `directory`, refusing it if it was
+	/// not recorded on the sources giving `hash`
+	pub fn load(directory: &str, level: &str, hash: u64) -> Result<Self, ReplayError> {
+		let text = fs::read_to_string(format!("{directory}/{}", Self::file_name(level)))
+			.map_err(ReplayError::Io)?;
+		let replay = Self::parse(&text)?;
+
+		if replay.hash != hash {
+			return Err(ReplayError::LevelMismatch);
+		}
+
+		Ok(replay)
+	}
+
+	pub fn save(&self, directory: &str) -> std::io::Result<()> {
+		fs::write(format!("{directory}/{}", Self::file_name(&self.level)), self.to_string())
+	}
+
+	fn parse(text: &str) -> Result<Self, ReplayError> {
+		let mut lines = text.lines().enumerate();
+		let mut header = |key: &str| -> Result<String, ReplayError> {
+			match lines.next() {
+				Some((i, line)) => line
+					.strip_prefix(key)
+					.map(|value| value.trim().to_owned())
+					.ok_or(ReplayError::Malformed(i + 1)),
+				None => Err(ReplayError::Malformed(0))
+			}
+		};
+
+		header(Self::MAGIC)?;
+		let level = header("level")?;
+		let hash = header("hash")?;
+		let version = header("version")?;
+
+		let mut r = Self {
+			level,
+			hash: u64::from_str_radix(&hash, 16).map_err(|_| ReplayError::Malformed(3))?,
+			version,
+			ticks: Vec::new()
+		};
+
+		for (i, line) in lines {
+			let mut words = line.split_whitespace();
+			let (jump, count) = match (words.next(), words.next(), words.next()) {
+				(Some(jump), Some(count), None) => (jump, count),
+				_ => return Err(ReplayError::Malformed(i + 1))
+			};
+			let state = InputState {
+				jump: match jump {
+					"0" => false,
+					"1" => true,
+					_ => return Err(ReplayError::Malformed(i + 1))
+				}
+			};
+			let count = count.parse::<usize>().map_err(|_| ReplayError::Malformed(i + 1))?;
+			r.ticks.extend(std::iter::repeat_n(state, count));
+		}
+
+		Ok(r)
+	}
+}
+
+impl fmt::Display for Replay {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}", Self::MAGIC)?;
+		writeln!(f, "level {}", self.level)?;
+		writeln!(f, "hash {:016x}", self.hash)?;
+		writeln!(f, "version {}", self.version)?;
+
+		for run in self.ticks.chunk_by(|a, b| a == b) {
+			writeln!(f, "{} {}", run[0].jump as u8, run.len())?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Records every input polled from its source
+pub struct Recorder {
+	source: Box::<dyn InputSource>,
+	ticks: Vec::<InputState>
+}
+
+impl Recorder {
+	pub fn new(source: Box::<dyn InputSource>) -> Self {
+		Self {
+			source,
+			ticks: Vec::new()
+		}
+	}
+
+	pub fn ticks(&self) -> &[InputState] { &self.ticks }
+}
+
+impl InputSource for Recorder {
+	fn poll(&mut self) -> InputState {
+		let r = self.source.poll();
+		self.ticks.push(r);
+		r
+	}
+
+	fn pause_requested(&mut self) -> bool { self.source.pause_requested() }
+
+	fn restart_requested(&mut self) -> bool { self.source.restart_requested() }
+}
+
+/// Plays a replay back, the keyboard can still pause and restart
+pub struct Playback {
+	script: ScriptedInput,
+	keyboard: Keyboard
+}
+
+impl From<&Replay> for Playback {
+	fn from(replay: &Replay) -> Self {
+		Self {
+			script: replay.input(),
+			keyboard: Keyboard
+		}
+	}
+}
+
+impl InputSource for Playback {
+	fn poll(&mut self) -> InputState { self.script.poll() }
+
+	fn pause_requested(&mut self) -> bool { self.keyboard.pause_requested() }
+
+	fn restart_requested(&mut self) -> bool { self.keyboard.restart_requested() }
+}
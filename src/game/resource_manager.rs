@@ -11,7 +11,9 @@ use std::collections::HashMap;
 #[derive(Debug)]
 pub enum Resource {
 	Texture (Texture2D),
-	Sound {sound: Sound, volume: Rc::<RefCell::<f32>>}
+	Sound {sound: Sound, volume: Rc::<RefCell::<f32>>},
+	/// Stands for any resource when running without window nor audio
+	Null
 }
 
 impl Resource {
@@ -27,6 +29,12 @@ impl Resource {
 			)
 		}
 	}
+
+	pub fn stop_if_sound(&self) {
+		if let Self::Sound {sound, ..} = self {
+			stop_sound(sound);
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -36,22 +44,42 @@ pub enum ResourceError {
 }
 
 pub struct ResourceManager {
-	resources: HashMap::<&'static str, Rc::<Resource>>,
-	volume: Rc::<RefCell<f32>>
+	resources: HashMap::<String, Rc::<Resource>>,
+	volume: Rc::<RefCell<f32>>,
+	null: bool
+}
+
+impl Default for ResourceManager {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl ResourceManager {
 	pub fn new() -> Self {
 		Self {
 			resources: Default::default(),
-			volume: Rc::new(RefCell::new(0.5))
+			volume: Rc::new(RefCell::new(0.5)),
+			null: false
+		}
+	}
+
+	/// Resource manager that never touches textures nor sounds, every request
+	/// gives a `Resource::Null`. Usable without any window.
+	pub fn null() -> Self {
+		Self {
+			null: true,
+			..Self::new()
 		}
 	}
 
-	pub async fn request(&mut self, path: &'static str) -> Result<Rc::<Resource>, ResourceError> {
+	pub async fn request(&mut self, path: &str) -> Result<Rc::<Resource>, ResourceError> {
 
 		if let Some(r) = self.resources.get(path) {
 			Ok(Rc::clone(r))
+		} else if self.null {
+			self.resources.insert(path.to_owned(), Rc::new(Resource::Null));
+			Ok(Rc::clone(&self.resources[path]))
 		} else {
 
 			let extension: String = path
@@ -69,7 +97,7 @@ impl ResourceManager {
 				"PNG" => match load_texture(path).await {
 					Ok(t) => {
 						t.set_filter(FilterMode::Nearest);
-						self.resources.insert(path, Rc::new(Resource::Texture(t)));
+						self.resources.insert(path.to_owned(), Rc::new(Resource::Texture(t)));
 						Ok(Rc::clone(&self.resources[path]))
 					},
 					Err(s) => {
@@ -78,7 +106,7 @@ impl ResourceManager {
 				},
 				"WAV" => match load_sound(path).await {
 					Ok(s) => {
-						self.resources.insert(path, Rc::new(Resource::Sound{sound: s, volume: Rc::clone(&self.volume)}));
+						self.resources.insert(path.to_owned(), Rc::new(Resource::Sound{sound: s, volume: Rc::clone(&self.volume)}));
 						Ok(Rc::clone(&self.resources[path]))
 					},
 					Err(e) => {
@@ -0,0 +1,166 @@
+//! Per-level statistics, persisted between sessions
+//!
+//! Saved in the user data directory as plain text:
+//! ```text
+//! [parkour.lvl]
+//! attempts 12
+//! best 8.533
+//! deaths 1
+//! death 640 28
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelStatistics {
+	pub attempts: u32,
+	/// Best completion time, in seconds
+	pub best_time: Option<f32>,
+	/// Every death, including the forgotten ones
+	pub death_count: u32,
+	/// Where the player died, oldest first, at most `MAX_DEATHS`
+	pub deaths: Vec::<Vec2>
+}
+
+impl LevelStatistics {
+	/// Deaths kept per level, so that the save file stays small
+	pub const MAX_DEATHS: usize = 1000;
+
+	/// Records a death, forgetting the oldest ones past `MAX_DEATHS`
+	pub fn add_death(&mut self, position: Vec2) {
+		self.death_count += 1;
+		self.deaths.push(position);
+		self.forget_old_deaths();
+	}
+
+	fn forget_old_deaths(&mut self) {
+		if self.deaths.len() > Self::MAX_DEATHS {
+			self.deaths.drain(..self.deaths.len() - Self::MAX_DEATHS);
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Statistics {
+	levels: HashMap::<String, LevelStatistics>
+}
+
+impl Statistics {
+	/// Loads statistics from the user data directory.
+	/// Missing or unreadable statistics are considered empty.
+	pub fn load() -> Self {
+		Self::path()
+			.and_then(|path| Self::load_from(&path).ok())
+			.unwrap_or_default()
+	}
+
+	pub fn load_from(path: &Path) -> std::io::Result<Self> {
+		Ok(Self::parse(&fs::read_to_string(path)?))
+	}
+
+	/// Saves statistics in the user data directory
+	pub fn save(&self) -> std::io::Result<()> {
+		match Self::path() {
+			Some(path) => self.save_to(&path),
+			None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no user data directory"))
+		}
+	}
+
+	pub fn save_to(&self, path: &Path) -> std::io::Result<()> {
+		if let Some(directory) = path.parent() {
+			fs::create_dir_all(directory)?;
+		}
+		fs::write(path, self.serialize())
+	}
+
+	pub fn level(&self, level: &str) -> Option<&LevelStatistics> {
+		self.levels.get(level)
+	}
+
+	pub fn level_mut(&mut self, level: &str) -> &mut LevelStatistics {
+		self.levels.entry(level.to_owned()).or_default()
+	}
+
+	/// Location of the save file, following each platform's convention
+	fn path() -> Option<PathBuf> {
+		let data = if cfg!(windows) {
+			std::env::var_os("APPDATA").map(PathBuf::from)
+		} else if cfg!(target_os = "macos") {
+			std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+		} else {
+			std::env::var_os("XDG_DATA_HOME")
+				.map(PathBuf::from)
+				.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+		};
+
+		data.map(|d| d.join("runner").join("statistics.txt"))
+	}
+
+	/// Unknown or malformed lines are ignored
+	fn parse(text: &str) -> Self {
+		let mut r = Self::default();
+		let mut current: Option<String> = None;
+
+		for line in text.lines().map(|l| l.trim()) {
+			if let Some(level) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+				r.levels.entry(level.to_owned()).or_default();
+				current = Some(level.to_owned());
+				continue;
+			}
+
+			let Some(stats) = current.as_ref().and_then(|level| r.levels.get_mut(level)) else {
+				continue;
+			};
+
+			let words: Vec::<&str> = line.split_whitespace().collect();
+			match words[..] {
+				["attempts", n] => if let Ok(n) = n.parse() {
+					stats.attempts = n;
+				},
+				["best", t] => if let Ok(t) = t.parse() {
+					stats.best_time = Some(t);
+				},
+				["deaths", n] => if let Ok(n) = n.parse() {
+					stats.death_count = n;
+				},
+				["death", x, y] => if let (Ok(x), Ok(y)) = (x.parse(), y.parse()) {
+					stats.deaths.push(vec2(x, y));
+				},
+				_ => {}
+			}
+		}
+
+		// Files written before deaths were counted only have positions
+		for stats in r.levels.values_mut() {
+			stats.death_count = stats.death_count.max(stats.deaths.len() as u32);
+			stats.forget_old_deaths();
+		}
+
+		r
+	}
+
+	fn serialize(&self) -> String {
+		let mut names: Vec::<&String> = self.levels.keys().collect();
+		names.sort();
+
+		let mut r = String::new();
+		for name in names {
+			let stats = &self.levels[name];
+			r += &format!("[{name}]\n");
+			r += &format!("attempts {}\n", stats.attempts);
+			if let Some(t) = stats.best_time {
+				r += &format!("best {t}\n");
+			}
+			r += &format!("deaths {}\n", stats.death_count);
+			for death in stats.deaths.iter() {
+				r += &format!("death {} {}\n", death.x, death.y);
+			}
+		}
+
+		r
+	}
+}
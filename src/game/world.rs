@@ -2,61 +2,359 @@
 
 use std::fs::File;
 use std::io::Read;
+use std::fmt;
+use std::sync::OnceLock;
 
 use macroquad::prelude::*;
 
 use super::resource_manager::*;
 use super::object::*;
-use tokenizer::Tokenizer;
-use interpretor::Interpretor;
+use super::input::*;
+use tokenizer::{Tokenizer, TokenizerError, Token};
+use interpretor::{Interpretor, InterpretorError};
+use diagnostic::{Diagnostic, Span};
+use super::replay::{Replay, ReplayError};
 
 pub mod tokenizer;
 pub mod interpretor;
+pub mod diagnostic;
+pub mod blueprint;
+pub mod expression;
+pub mod metadata;
 
 
+/// Any reason for a level not to load
+#[derive(Debug)]
+pub enum LevelLoadError {
+	Io (String, std::io::Error),
+	Tokenizer {level: String, code: String, error: TokenizerError},
+	Interpretor {level: String, code: String, error: InterpretorError},
+	Replay (String, ReplayError),
+	/// Levels including each other, the first one being included again last
+	IncludeCycle (Vec::<String>),
+	/// Error in a file included by the level, through the given chain of includes
+	Included {chain: Vec::<String>, error: Box<LevelLoadError>},
+	/// Start requested at a checkpoint the level does not have
+	NoCheckpoint {level: String, checkpoint: usize, count: usize}
+}
+
+impl fmt::Display for LevelLoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io(level, e) => write!(f, "error: cannot read level `{level}`: {e}"),
+			Self::Tokenizer {level, code, error} => write!(f, "{}", error.render(level, code)),
+			Self::Interpretor {level, code, error} => write!(f, "{}", error.render(level, code)),
+			Self::Replay(level, e) => write!(f, "error: cannot watch replay of `{level}`: {e}"),
+			Self::IncludeCycle(chain) => write!(f, "error: include cycle: {}", chain.join(" -> ")),
+			Self::Included {chain, error} => write!(f, "{}\n  = note: included through {}", error.to_string().trim_end(), chain.join(" -> ")),
+			Self::NoCheckpoint {level, checkpoint, count} => write!(f, "error: cannot start at checkpoint {checkpoint}, `{level}` has {count}")
+		}
+	}
+}
+
+/// Outcome of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+	Playing,
+	Dead,
+	Complete
+}
+
+/// Where the player starts a level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Start {
+	/// Checkpoint of the level, counted from 1 in declaration order
+	Checkpoint (usize),
+	/// Position in pixels
+	Position (Vec2)
+}
+
+/// Directory of the level files, see `World::set_levels_dir()`
+static LEVELS_DIR: OnceLock<String> = OnceLock::new();
+
+/// Source code of a level or of a file it includes
+struct SourceFile {
+	name: String,
+	code: String,
+	/// Includes leading to this file, starting with the level
+	chain: Vec::<String>
+}
+
+impl SourceFile {
+	/// Reports `error` as coming from this file
+	fn error(&self, error: LevelLoadError) -> LevelLoadError {
+		if self.chain.len() > 1 {
+			LevelLoadError::Included {chain: self.chain.clone(), error: Box::new(error)}
+		} else {
+			error
+		}
+	}
+}
+
 /// Abstracts a level
 #[derive(Debug)]
 pub struct World {
 	objects: Vec::<Object>,
+	/// Last unit declared in the level source
+	unit: Vec2,
+	metadata: metadata::Metadata,
 	camera: Camera2D,
-	playing: bool
+	previous_target: Vec2,
+	status: RunStatus,
+	checkpoint: Option<Snapshot>,
+	accumulator: f32,
+	ticks: u32,
+	/// Hash of the level source and of its includes, see `Replay::hash()`
+	hash: u64
+}
+
+
+/// Canonical level source of the world, see `Blueprint`
+impl fmt::Display for World {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		blueprint::Blueprint::from(self).fmt(f)
+	}
 }
 
+impl Default for World {
+	fn default() -> Self {
+		Self::new()
+	}
+}
 
 impl World {
+	/// Duration of a simulation tick, in seconds
+	pub const TICK: f32 = 1. / 60.;
+
+	/// Size of the visible part of the world, in pixels
+	const VIEW: Vec2 = vec2(800., 450.);
+
+	/// Longest frame time taken into account, avoids spiraling
+	/// after a freeze
+	const MAX_FRAME_TIME: f32 = 0.25;
+
+	/// Directory the levels, their includes and replays are read from
+	pub fn levels_dir() -> &'static str {
+		LEVELS_DIR.get().map_or("res/levels", String::as_str)
+	}
+
+	/// Overrides the levels directory, only once and before any level is read.
+	/// Returns false if it was already set.
+	pub fn set_levels_dir(directory: &str) -> bool {
+		LEVELS_DIR.set(directory.trim_end_matches('/').to_owned()).is_ok()
+	}
 
 	/// Loads level from file
-	/// TODO: Handle errors more cleanly
-	pub async fn from(arg: (&str, &mut ResourceManager)) -> Self {
+	pub async fn from(arg: (&str, &mut ResourceManager)) -> Result<Self, LevelLoadError> {
+		Self::from_source(arg.0, &Self::read(arg.0)?, arg.1).await
+	}
+
+	/// Loads a level file from anywhere, its includes being relative to its directory
+	pub async fn from_file(path: &str, rm: &mut ResourceManager) -> Result<Self, LevelLoadError> {
+		let file = std::path::Path::new(path);
+		let directory = match file.parent().and_then(|d| d.to_str()) {
+			Some("") | None => ".",
+			Some(directory) => directory
+		};
+		let source = std::fs::read_to_string(file).map_err(|e| LevelLoadError::Io(path.to_owned(), e))?;
+		Self::from_source_in(directory, path, &source, rm).await
+	}
+
+	/// Reads the source code of a level
+	pub fn read(level: &str) -> Result<String, LevelLoadError> {
+		Self::read_in(Self::levels_dir(), level)
+	}
+
+	/// Reads the source code of a level in `directory`
+	fn read_in(directory: &str, level: &str) -> Result<String, LevelLoadError> {
 		let mut s = String::new();
-		File::open(&format!("res/levels/{}", arg.0)).unwrap().read_to_string(&mut s).unwrap();
-		Interpretor::interpret(
-			Tokenizer::tokenize(&s, arg.1).await.unwrap()
-		).unwrap()
+		File::open(format!("{directory}/{level}"))
+			.and_then(|mut f| f.read_to_string(&mut s))
+			.map_err(|e| LevelLoadError::Io(level.to_owned(), e))?;
+		Ok(s)
+	}
+
+	/// Loads level from its source code, `level` is only used in error reports
+	/// and to detect include cycles. Included files are read from the levels directory.
+	pub async fn from_source(level: &str, source: &str, rm: &mut ResourceManager) -> Result<Self, LevelLoadError> {
+		Self::from_source_in(Self::levels_dir(), level, source, rm).await
+	}
+
+	/// Loads level from its source code, included files being read from `directory`
+	async fn from_source_in(directory: &str, level: &str, source: &str, rm: &mut ResourceManager) -> Result<Self, LevelLoadError> {
+		let mut files = Vec::<SourceFile>::new();
+		let tokens = Self::tokenize(directory, level, source, vec![level.to_owned()], &mut files, rm).await?;
+
+		let mut world = Interpretor::interpret(tokens).map_err(|error| {
+			let file = &files[error.span().file];
+			file.error(LevelLoadError::Interpretor {
+				level: file.name.clone(),
+				code: file.code.clone(),
+				error
+			})
+		})?;
+		world.hash = Replay::hash(files.iter().map(|f| f.code.as_str()));
+		Ok(world)
+	}
+
+	/// Metadata of a level file, loaded without any resource
+	pub fn read_metadata(level: &str) -> Result<metadata::Metadata, LevelLoadError> {
+		let world = super::headless::block_on(Self::from((level, &mut ResourceManager::null())))?;
+		Ok(world.metadata.clone())
+	}
+
+	pub fn metadata(&self) -> &metadata::Metadata { &self.metadata }
+
+	/// Identifies the level source and its includes, replays recorded on
+	/// other sources are refused
+	pub fn hash(&self) -> u64 { self.hash }
+
+	/// Tokenizes a source file, replacing includes with the tokens of the included files
+	async fn tokenize(
+		directory: &str,
+		name: &str,
+		code: &str,
+		chain: Vec::<String>,
+		files: &mut Vec::<SourceFile>,
+		rm: &mut ResourceManager
+	) -> Result<Vec::<(Token, Span)>, LevelLoadError> {
+		let index = files.len();
+		files.push(SourceFile {name: name.to_owned(), code: code.to_owned(), chain});
+
+		let tokens = Tokenizer::tokenize(code, rm).await.map_err(|error| files[index].error(LevelLoadError::Tokenizer {
+			level: name.to_owned(),
+			code: code.to_owned(),
+			error
+		}))?;
+
+		let mut r = Vec::<(Token, Span)>::new();
+		for (t, mut span) in tokens {
+			span.file = index;
+			if let Token::Include(included) = t {
+				let mut chain = files[index].chain.clone();
+				let cycle = chain.contains(&included);
+				chain.push(included.clone());
+				if cycle {
+					return Err(LevelLoadError::IncludeCycle(chain));
+				}
+
+				let source = Self::read_in(directory, &included).map_err(|e| LevelLoadError::Included {chain: chain.clone(), error: Box::new(e)})?;
+				let mut tokens = Box::pin(Self::tokenize(directory, &included, &source, chain, files, rm)).await?;
+				// Only the end of the level is kept
+				tokens.pop();
+				r.append(&mut tokens);
+			} else {
+				r.push((t, span));
+			}
+		}
+
+		Ok(r)
 	}
 
 	/// Default empty world constructor
 	pub fn new() -> Self {
 		Self {
 			objects: Vec::<Object>::new(),
+			unit: Vec2::ONE,
+			metadata: Default::default(),
 			camera: Camera2D {
-				zoom: vec2(1./400., 1./225.),
+				zoom: vec2(2. / Self::VIEW.x, 2. / Self::VIEW.y),
 				..Default::default()
 			},
-			playing: true
+			previous_target: Vec2::ZERO,
+			status: RunStatus::Playing,
+			checkpoint: None,
+			accumulator: 0.,
+			ticks: 0,
+			hash: 0
 		}
 	}
 
-	pub fn is_playing(&self) -> bool { self.playing } 
+	pub fn is_playing(&self) -> bool { self.status == RunStatus::Playing }
+
+	pub fn status(&self) -> RunStatus { self.status }
+
+	/// Number of ticks spent playing
+	pub fn ticks(&self) -> u32 { self.ticks }
 
 	pub fn clear(&mut self) { self.objects.clear() }
 
-	/// Broadcasts the update call on every object of world, handle collisions
-	/// and move camera.
-	/// Has to be called once per game loop
-	pub fn update(&mut self) {
+	pub fn has_checkpoint(&self) -> bool { self.checkpoint.is_some() }
+
+	/// Moves the players to `start`. Starting at a checkpoint also reaches it.
+	/// `level` is only used in error reports.
+	pub fn start_at(&mut self, level: &str, start: Start) -> Result<(), LevelLoadError> {
+		let position = match start {
+			Start::Position(position) => position,
+			Start::Checkpoint(checkpoint) => {
+				let checkpoints: Vec::<&Object> = self.objects
+					.iter()
+					.filter(|o| matches!(o.kind, ObjectKind::Checkpoint))
+					.collect();
+				let Some(reached) = checkpoint.checked_sub(1).and_then(|i| checkpoints.get(i)) else {
+					return Err(LevelLoadError::NoCheckpoint {level: level.to_owned(), checkpoint, count: checkpoints.len()});
+				};
+				// Standing on the bottom of the checkpoint
+				reached.position + vec2(0., reached.size.y - self.player().map_or(0., |p| p.size.y))
+			}
+		};
+
+		for o in self.objects.iter_mut() {
+			if let ObjectKind::Player {..} = o.kind {
+				o.teleport(position);
+				if let Start::Checkpoint(_) = start {
+					self.checkpoint = Some(o.snapshot());
+				}
+				self.camera.target = Self::camera_target(o);
+				self.previous_target = self.camera.target;
+			}
+		}
+		Ok(())
+	}
+
+	/// Restores the player from the last checkpoint reached.
+	/// Returns false if no checkpoint was reached.
+	pub fn respawn(&mut self) -> bool {
+		if let Some(snapshot) = &self.checkpoint {
+			for o in self.objects.iter_mut() {
+				if let ObjectKind::Player {..} = o.kind {
+					o.restore(snapshot);
+				}
+			}
+			self.status = RunStatus::Playing;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Runs as many simulation ticks as fit in the time elapsed.
+	/// Has to be called once per game loop with the frame time.
+	/// `input` is polled once per tick.
+	pub fn update(&mut self, dt: f32, input: &mut dyn InputSource) {
+		self.accumulator += dt.min(Self::MAX_FRAME_TIME);
+
+		while self.accumulator >= Self::TICK {
+			self.step(input.poll());
+			self.accumulator -= Self::TICK;
+		}
+	}
+
+	/// Runs exactly one simulation tick: broadcasts the update call on every
+	/// object of world, handle collisions and move camera.
+	pub fn step(&mut self, input: InputState) {
+
+		if self.is_playing() {
+			self.ticks += 1;
+		}
+
+		self.previous_target = self.camera.target;
+		for o in self.objects.iter_mut() {
+			o.begin_tick();
+		}
 
-		self.playing = false;
+		// Once the run is over, its outcome can no longer change
+		let mut status = RunStatus::Dead;
 
 		for i in 0..self.objects.len() {			
 			// Collision code
@@ -65,31 +363,88 @@ impl World {
 				splitted.0.last_mut().unwrap().collide(o);
 			}
 			
-			self.objects[i].update();
+			self.objects[i].update(&input);
+
+			if self.objects[i].take_checkpoint() {
+				self.checkpoint = Some(self.objects[i].snapshot());
+			}
 			
 			// Camera movement
 			if let ObjectKind::Player {..} = self.objects[i].kind {
 				self.camera.target = self.camera.target + (
-					self.objects[i].position + self.objects[i].size/2. + vec2(screen_width() * 2./7., 0.) -
-					self.camera.target
+					Self::camera_target(&self.objects[i]) - self.camera.target
 				) * 0.1;
 
-				if self.objects[i].is_alive() { self.playing = true; }
+				if self.objects[i].is_finished() {
+					status = RunStatus::Complete;
+				} else if self.objects[i].is_alive() && status == RunStatus::Dead {
+					status = RunStatus::Playing;
+				}
 			}
 		}
+
+		if self.status == RunStatus::Playing {
+			self.status = status;
+		}
+	}
+
+	/// Point the camera follows, ahead of `player`
+	fn camera_target(player: &Object) -> Vec2 {
+		player.position + player.size/2. + vec2(Self::VIEW.x * 2./7., 0.)
+	}
+
+	/// Copy of this world whose objects are ghosts, see Object::ghost()
+	pub fn ghost(&self) -> Self {
+		let mut r = Self::new();
+		r.unit = self.unit;
+		for o in self.objects.iter() {
+			r.add_object(o.clone().ghost(true));
+		}
+		r
 	}
 
-	/// Broadcasts the draw call on every object in world
+	/// Camera between the last two simulation ticks
+	fn view(&self) -> Camera2D {
+		Camera2D {
+			zoom: self.camera.zoom,
+			target: self.previous_target.lerp(self.camera.target, self.alpha()),
+			..Default::default()
+		}
+	}
+
+	/// Progression of the current simulation tick
+	fn alpha(&self) -> f32 { self.accumulator / Self::TICK }
+
+	/// Broadcasts the draw call on every object in world, interpolating
+	/// between the last two simulation ticks
 	pub fn draw(&mut self) {
+		let alpha = self.alpha();
+
+		set_camera(&self.view());
+		for o in self.objects.iter_mut() {
+			o.draw(alpha);
+		}
+		set_default_camera();
+	}
 
-		set_camera(&self.camera);
+	/// Draws only the players of this world, as seen in `view`
+	pub fn draw_players(&mut self, view: &World) {
+		let alpha = self.alpha();
 
+		set_camera(&view.view());
 		for o in self.objects.iter_mut() {
-			o.draw();
+			if let ObjectKind::Player {..} = o.kind {
+				o.draw(alpha);
+			}
 		}
 		set_default_camera();
 	}
 
+	/// First player of the world, if any
+	pub fn player(&self) -> Option<&Object> {
+		self.objects.iter().find(|o| matches!(o.kind, ObjectKind::Player {..}))
+	}
+
 	pub fn add_object(&mut self, o: Object) {
 		self.objects.push(o);
 	}
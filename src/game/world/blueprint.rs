@@ -0,0 +1,195 @@
+//! Resource-free description of a level, as written in its source
+
+use std::fmt;
+
+use macroquad::prelude::*;
+
+use super::World;
+use super::metadata::Metadata;
+use super::super::object::{Object, ObjectKind};
+
+/// Any object of a level, positions and sizes are in pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piece {
+	pub keyword: String,
+	pub position: Vec2,
+	pub size: Vec2,
+	pub speed: Vec2,
+	pub gravity: f32,
+	/// Path of moving platforms
+	pub route: Option<Route>,
+	/// Horizontal speed given by speed portals, in pixels per tick
+	pub portal_speed: f32
+}
+
+/// Path of a moving platform, its waypoints being relative to its position
+/// so that it follows the platform in the editor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+	pub waypoints: Vec::<Vec2>,
+	/// In pixels per tick
+	pub speed: f32,
+	pub looping: bool
+}
+
+impl Piece {
+	pub fn new(keyword: &str, position: Vec2, size: Vec2) -> Self {
+		Self {
+			keyword: keyword.to_owned(),
+			position,
+			size,
+			speed: Vec2::ZERO,
+			gravity: Object::GRAVITY,
+			route: None,
+			portal_speed: Object::PORTAL_SPEED
+		}
+	}
+
+	pub fn contains(&self, v: Vec2) -> bool {
+		   self.position.x <= v.x
+		&& self.position.x + self.size.x >= v.x
+		&& self.position.y <= v.y
+		&& self.position.y + self.size.y >= v.y
+	}
+}
+
+/// Editable level, turned back into source code through `Display`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blueprint {
+	pub metadata: Metadata,
+	pub unit: Vec2,
+	pub pieces: Vec::<Piece>
+}
+
+impl Blueprint {
+	pub fn new(unit: Vec2) -> Self {
+		Self {
+			metadata: Metadata::default(),
+			unit,
+			pieces: Vec::new()
+		}
+	}
+
+	/// Index of the topmost piece containing `v`
+	pub fn piece_at(&self, v: Vec2) -> Option<usize> {
+		self.pieces.iter().rposition(|p| p.contains(v))
+	}
+
+	/// Unit in which every position and size can be written as a whole
+	/// number: the blueprint's own if possible, else the greatest common
+	/// divisor of the values, which is one pixel at worst
+	pub fn infer_unit(&self) -> Vec2 {
+		let values = |axis: fn(Vec2) -> f32| self.pieces
+			.iter()
+			.flat_map(move |p| [axis(p.position), axis(p.size)].into_iter().chain(
+				p.route.iter().flat_map(move |r| r.waypoints.iter().map(move |w| axis(p.position + *w)))
+			));
+
+		let infer = |axis: fn(Vec2) -> f32| {
+			let unit = axis(self.unit);
+			if unit > 0. && values(axis).all(|v| (v / unit).fract() == 0.) {
+				return unit;
+			}
+			if values(axis).any(|v| v.fract() != 0.) {
+				return 1.;
+			}
+			match values(axis).fold(0, |r, v| gcd(r, v.abs() as u32)) {
+				0 => 1.,
+				r => r as f32
+			}
+		};
+
+		vec2(infer(|v| v.x), infer(|v| v.y))
+	}
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+	if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// `x` rounded to the significant digits any literal keeps, so that
+/// converting between units does not write `0.30000001`
+fn rounded(x: f32) -> f32 {
+	format!("{x:.*e}", f32::DIGITS as usize - 1).parse().unwrap_or(x)
+}
+
+impl From<&World> for Blueprint {
+	fn from(world: &World) -> Self {
+		Self {
+			metadata: world.metadata.clone(),
+			unit: world.unit,
+			pieces: world.objects
+				.iter()
+				.map(|o| Piece {
+					keyword: o.kind.keyword().to_owned(),
+					position: o.position,
+					size: o.size,
+					speed: o.get_speed(),
+					gravity: o.get_gravity(),
+					route: match &o.kind {
+						ObjectKind::Platform(path) if path.waypoints.len() > 1 => Some(Route {
+							waypoints: path.waypoints[1..].iter().map(|w| *w - path.waypoints[0]).collect(),
+							speed: path.speed,
+							looping: path.looping
+						}),
+						_ => None
+					},
+					portal_speed: match o.kind {
+						ObjectKind::SpeedPortal(speed) => speed,
+						_ => Object::PORTAL_SPEED
+					}
+				})
+				.collect()
+		}
+	}
+}
+
+/// Writes the level in canonical world definition language, in the
+/// inferred unit. Pieces keep their order, as only objects declared after
+/// players collide with them.
+impl fmt::Display for Blueprint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let unit = self.infer_unit();
+		write!(f, "{}", self.metadata)?;
+		writeln!(f, "Unit is ({}, {})", unit.x, unit.y)?;
+
+		for p in self.pieces.iter() {
+			let position = p.position / unit;
+			let size = p.size / unit;
+
+			write!(f, "{} at ({}, {})", p.keyword, position.x, position.y)?;
+			if size != Vec2::ONE {
+				write!(f, " of size ({}, {})", size.x, size.y)?;
+			}
+			if p.speed.y != 0. {
+				// Vectors are always in units
+				let speed = p.speed / unit;
+				write!(f, " with initial speed of ({}, {})", rounded(speed.x), rounded(speed.y))?;
+			} else if p.speed.x != 0. {
+				write!(f, " with initial speed of {}px", p.speed.x)?;
+			}
+			if p.gravity != Object::GRAVITY {
+				write!(f, " with gravity of {}px", p.gravity)?;
+			}
+			if p.keyword == "SpeedPortal" && p.portal_speed != Object::PORTAL_SPEED {
+				write!(f, " with speed of {}px", p.portal_speed)?;
+			}
+			match &p.route {
+				Some(route) if p.keyword == "Platform" => {
+					for (i, w) in route.waypoints.iter().enumerate() {
+						let waypoint = (p.position + *w) / unit;
+						write!(f, " {} ({}, {})", if i == 0 { "moving to" } else { "then" }, waypoint.x, waypoint.y)?;
+					}
+					write!(f, " at {}px", route.speed)?;
+					if route.looping {
+						write!(f, " looping")?;
+					}
+				},
+				_ => {}
+			}
+			writeln!(f)?;
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,71 @@
+//! Human readable error reporting for the world definition language
+
+/// Location of a piece of source code.
+/// Lines and columns start at 1, columns and length are counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+	pub line: usize,
+	pub column: usize,
+	pub length: usize,
+	/// Index of the source file among the level and its includes, 0 being the level
+	pub file: usize
+}
+
+impl Span {
+	pub fn new(line: usize, column: usize, length: usize) -> Self {
+		Self { line, column, length, file: 0 }
+	}
+
+	/// Smallest span covering both `self` and `other`
+	pub fn to(&self, other: Span) -> Self {
+		if self.line == other.line {
+			Self { length: other.column + other.length - self.column, ..*self }
+		} else {
+			*self
+		}
+	}
+}
+
+/// Any error that can be pointed at in a level source
+pub trait Diagnostic {
+	fn span(&self) -> Span;
+	fn message(&self) -> String;
+
+	/// Renders the error with the offending line and a caret under it
+	/// # Example
+	/// ```text
+	/// error: unknown token `(0;1)`
+	///  --> lab.lvl:3:9
+	///   |
+	/// 3 | Wall at (0; 1) of size (10, 10)
+	///   |         ^^^^^^
+	/// ```
+	fn render(&self, file: &str, source: &str) -> String {
+		let span = self.span();
+		let mut r = format!(
+			"error: {}\n --> {}:{}:{}\n",
+			self.message(),
+			file,
+			span.line,
+			span.column
+		);
+
+		if let Some(line) = source.lines().nth(span.line.saturating_sub(1)) {
+			let number = span.line.to_string();
+			let margin = " ".repeat(number.len());
+
+			// Keeping tabulations so that the caret stays aligned
+			let padding: String = line
+				.chars()
+				.take(span.column.saturating_sub(1))
+				.map(|c| if c == '\t' { '\t' } else { ' ' })
+				.collect();
+
+			r += &format!("{margin} |\n");
+			r += &format!("{number} | {line}\n");
+			r += &format!("{margin} | {padding}{}\n", "^".repeat(span.length.max(1)));
+		}
+
+		r
+	}
+}
@@ -0,0 +1,153 @@
+//! Arithmetic in literals, evaluated when the level is interpreted
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+	Add,
+	Subtract,
+	Multiply,
+	Divide
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+	Number (f32),
+	Constant (String),
+	Negate (Box<Expression>),
+	Binary (Box<Expression>, Operator, Box<Expression>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionError {
+	UnknownConstant (String),
+	DivisionByZero
+}
+
+impl fmt::Display for ExpressionError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::UnknownConstant(name) => write!(f, "unknown constant `{name}`"),
+			Self::DivisionByZero => write!(f, "division by zero")
+		}
+	}
+}
+
+impl From<f32> for Expression {
+	fn from(x: f32) -> Self {
+		Self::Number(x)
+	}
+}
+
+impl Expression {
+	/// Parses blank-free source such as `2*(gap+1)`, `None` if it is not
+	/// a valid expression
+	pub fn parse(s: &str) -> Option<Self> {
+		let chars: Vec::<char> = s.chars().collect();
+		let mut parser = Parser {chars: &chars, i: 0};
+		let r = parser.sum()?;
+		if parser.i == chars.len() { Some(r) } else { None }
+	}
+
+	/// Whether `s` can be the name of a constant
+	pub fn is_name(s: &str) -> bool {
+		let mut chars = s.chars();
+		chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+			&& chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+	}
+
+	pub fn evaluate(&self, constants: &HashMap<String, f32>) -> Result<f32, ExpressionError> {
+		match self {
+			Self::Number(x) => Ok(*x),
+			Self::Constant(name) => constants
+				.get(name)
+				.copied()
+				.ok_or_else(|| ExpressionError::UnknownConstant(name.clone())),
+			Self::Negate(e) => Ok(-e.evaluate(constants)?),
+			Self::Binary(a, op, b) => {
+				let a = a.evaluate(constants)?;
+				let b = b.evaluate(constants)?;
+				match op {
+					Operator::Add => Ok(a + b),
+					Operator::Subtract => Ok(a - b),
+					Operator::Multiply => Ok(a * b),
+					Operator::Divide if b == 0. => Err(ExpressionError::DivisionByZero),
+					Operator::Divide => Ok(a / b)
+				}
+			}
+		}
+	}
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser<'a> {
+	chars: &'a [char],
+	i: usize
+}
+
+impl Parser<'_> {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.i).copied()
+	}
+
+	fn sum(&mut self) -> Option<Expression> {
+		let mut r = self.product()?;
+		while let Some(op) = match self.peek() {
+			Some('+') => Some(Operator::Add),
+			Some('-') => Some(Operator::Subtract),
+			_ => None
+		} {
+			self.i += 1;
+			r = Expression::Binary(Box::new(r), op, Box::new(self.product()?));
+		}
+		Some(r)
+	}
+
+	fn product(&mut self) -> Option<Expression> {
+		let mut r = self.factor()?;
+		while let Some(op) = match self.peek() {
+			Some('*') => Some(Operator::Multiply),
+			Some('/') => Some(Operator::Divide),
+			_ => None
+		} {
+			self.i += 1;
+			r = Expression::Binary(Box::new(r), op, Box::new(self.factor()?));
+		}
+		Some(r)
+	}
+
+	fn factor(&mut self) -> Option<Expression> {
+		match self.peek()? {
+			'-' => {
+				self.i += 1;
+				Some(Expression::Negate(Box::new(self.factor()?)))
+			},
+			'(' => {
+				self.i += 1;
+				let r = self.sum()?;
+				if self.peek()? != ')' {
+					return None;
+				}
+				self.i += 1;
+				Some(r)
+			},
+			'0' ..= '9' | '.' => {
+				let start = self.i;
+				while matches!(self.peek(), Some('0' ..= '9' | '.')) {
+					self.i += 1;
+				}
+				let number: String = self.chars[start..self.i].iter().collect();
+				number.parse().ok().map(Expression::Number)
+			},
+			c if c.is_ascii_alphabetic() || c == '_' => {
+				let start = self.i;
+				while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_') {
+					self.i += 1;
+				}
+				Some(Expression::Constant(self.chars[start..self.i].iter().collect()))
+			},
+			_ => None
+		}
+	}
+}
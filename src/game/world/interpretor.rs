@@ -1,7 +1,12 @@
 //! Syntaxic analyzer
 
+use std::collections::HashMap;
+
 use macroquad::prelude::*;
 use super::tokenizer::{Unit, Token};
+use super::diagnostic::*;
+use super::expression::*;
+use super::metadata::*;
 use super::World;
 use super::super::object::Object;
 use super::super::object::ObjectKind;
@@ -12,115 +17,563 @@ enum InterpretorState {
 	Initial,
 	UnitDeclaration,
 	UnitDefinition,
+	BindingDefinition (String),
+	MetadataDeclaration (Field),
+	MetadataDefinition (Field),
 	ObjectDeclaration (Object),
 	PositionDefinition (Object),
 	InitialSpeedDefinition (Object),
+	GravityDefinition (Object),
 	SizeDefinition (Object),
+	WaypointDefinition (Object),
+	PathContinuation (Object),
+	PathSpeedDefinition (Object),
+	PortalSpeedDefinition (Object),
+	RepeatCount,
+	RepeatTimes (usize),
+	RepeatStep (usize),
+	RepeatOffset (usize),
+	RepeatBlock (usize, Vec2),
+	PrefabDefinition (String),
+	PlaceAt (String, Span),
+	PlacePosition (String, Span),
 	Done
 }
 
+/// A `repeat` block or a prefab being instantiated
+struct Block {
+	/// Index of the first token of the block
+	start: usize,
+	count: usize,
+	iteration: usize,
+	/// Offset between iterations, in pixels
+	step: Vec2,
+	/// Position of the placed prefab, in pixels
+	origin: Vec2,
+	/// Placed prefab and index of the token to read once it is placed
+	placement: Option<(String, usize)>,
+	line: usize,
+	/// Constants before the block, bindings made in it last until its end
+	constants: HashMap::<String, f32>
+}
+
+/// Everything the automaton builds while reading tokens
+struct Context {
+	world: World,
+	unit: Vec2,
+	constants: HashMap::<String, f32>,
+	/// Index of the first token of each prefab
+	prefabs: HashMap::<String, usize>,
+	blocks: Vec::<Block>,
+	/// Index of the next token to read, moved back at the end of blocks
+	next: usize,
+	/// Iterations of every loop so far, see `Interpretor::MAX_ITERATIONS`
+	iterations: usize
+}
+
 #[derive(Debug)]
-pub struct InterpretorError {
-	unexpected: Token,
-	expected: Vec::<Token>
+pub enum InterpretorError {
+	Unexpected {unexpected: Box<Token>, span: Span, expected: Vec::<Token>},
+	Expression (ExpressionError, Span),
+	InvalidCount (f32, Span),
+	InvalidSpeed (f32, Span),
+	/// Constant bound to a value with a unit
+	UnitInBinding (String, Span),
+	/// Loops repeating more than the given number of times in total
+	TooManyIterations (usize, Span),
+	UnknownPrefab (String, Span),
+	InvalidMetadata (Field, String, Span),
+	RecursivePrefab (String, Span),
+	/// Error in a given iteration, counted from 1, of the loop at a given line
+	Iteration {error: Box<InterpretorError>, iteration: usize, count: usize, line: usize},
+	/// Error in a prefab placed at a given line
+	Placement {error: Box<InterpretorError>, prefab: String, line: usize}
+}
+
+impl Diagnostic for InterpretorError {
+	fn span(&self) -> Span {
+		match self {
+			Self::Unexpected {span, ..}
+			| Self::Expression(_, span)
+			| Self::InvalidCount(_, span)
+			| Self::InvalidSpeed(_, span)
+			| Self::UnitInBinding(_, span)
+			| Self::TooManyIterations(_, span)
+			| Self::UnknownPrefab(_, span)
+			| Self::InvalidMetadata(_, _, span)
+			| Self::RecursivePrefab(_, span) => *span,
+			Self::Iteration {error, ..}
+			| Self::Placement {error, ..} => error.span()
+		}
+	}
+
+	fn message(&self) -> String {
+		match self {
+			Self::Unexpected {unexpected, expected, ..} => {
+				let mut names = Vec::<String>::new();
+				for e in expected.iter().map(|e| e.to_string()) {
+					if !names.contains(&e) {
+						names.push(e);
+					}
+				}
+
+				match names.len() {
+					0 => format!("unexpected {unexpected}"),
+					1 => format!("unexpected {unexpected}, expected {}", names[0]),
+					_ => format!("unexpected {unexpected}, expected one of {}", names.join(", "))
+				}
+			},
+			Self::Expression(e, _) => e.to_string(),
+			Self::InvalidCount(x, _) => format!("cannot repeat {x} times, expected a whole number"),
+			Self::InvalidSpeed(x, _) => format!("invalid speed {x}, expected a positive number"),
+			Self::UnitInBinding(name, _) => format!("constant `{name}` cannot have a unit"),
+			Self::TooManyIterations(max, _) => format!("loops repeat more than {max} times in total"),
+			Self::UnknownPrefab(name, _) => format!("unknown prefab `{name}`"),
+			Self::InvalidMetadata(field, value, _) => match field {
+				Field::Difficulty => format!("invalid difficulty {value}, expected a whole number"),
+				Field::Background => format!("invalid color `{value}`, expected `#rrggbb`"),
+				_ => format!("invalid {} `{value}`", field.keyword())
+			},
+			Self::RecursivePrefab(name, _) => format!("prefab `{name}` places itself"),
+			Self::Iteration {error, iteration, count, line} => format!("{} (iteration {iteration} of {count} of the loop at line {line})", error.message()),
+			Self::Placement {error, prefab, line} => format!("{} (in prefab `{prefab}` placed at line {line})", error.message())
+		}
+	}
 }
 
 pub struct Interpretor;
 impl Interpretor {
+	/// Total iterations of the loops of a level, nested ones included,
+	/// keeps levels quick to load
+	const MAX_ITERATIONS: usize = 100_000;
+
 	/// TODO: Replace `Vec::<Token>` with an always valid type.
 	/// i.e.: That type should be only created by Tokenizer::tokenize 
-	pub fn interpret(tokens: Vec::<Token>) -> Result<World, InterpretorError> {
-		let mut r = World::new();
-		let mut unit = Vec2::new(1., 1.);
+	pub fn interpret(tokens: Vec::<(Token, Span)>) -> Result<World, InterpretorError> {
+		let mut c = Context {
+			world: World::new(),
+			unit: Vec2::new(1., 1.),
+			constants: HashMap::new(),
+			prefabs: HashMap::new(),
+			blocks: Vec::new(),
+			next: 0,
+			iterations: 0
+		};
 		let mut state = InterpretorState::Initial;
-		
-		for t in tokens {
-
-			// Complete, deterministic, simple automaton.
-			state = match state {
-				InterpretorState::Initial => match t {
-					Token::Unit => InterpretorState::UnitDeclaration,
-					Token::Kind(k) => InterpretorState::ObjectDeclaration (Object::from(k)),
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![
-							Token::Unit,
-							Token::Kind(ObjectKind::Spike),
-							Token::Kind(ObjectKind::Wall)
-						]
-					})
+
+		while let Some((t, span)) = tokens.get(c.next).cloned() {
+			c.next += 1;
+			state = match Self::transition(&mut c, &tokens, state, t, span) {
+				Ok(state) => state,
+				Err(mut error) => {
+					// Innermost block first
+					for b in c.blocks.iter().rev() {
+						error = match &b.placement {
+							Some((prefab, _)) => InterpretorError::Placement {
+								error: Box::new(error),
+								prefab: prefab.clone(),
+								line: b.line
+							},
+							None => InterpretorError::Iteration {
+								error: Box::new(error),
+								iteration: b.iteration + 1,
+								count: b.count,
+								line: b.line
+							}
+						};
+					}
+					return Err(error);
+				}
+			};
+		}
+
+		Ok(c.world)
+	}
+
+	/// Starts a statement, in the initial state or right after an object declaration
+	fn statement(c: &mut Context, t: Token, span: Span) -> Result<InterpretorState, InterpretorError> {
+		Ok(match t {
+			Token::Unit => InterpretorState::UnitDeclaration,
+			Token::Let(name) => InterpretorState::BindingDefinition(name),
+			Token::Metadata(field) => InterpretorState::MetadataDeclaration(field),
+			Token::Kind(k) => InterpretorState::ObjectDeclaration (Object::from(k)),
+			Token::Repeat => InterpretorState::RepeatCount,
+			Token::Prefab(name) => InterpretorState::PrefabDefinition(name),
+			Token::Place(name) => InterpretorState::PlaceAt(name, span),
+			Token::BlockEnd if !c.blocks.is_empty() => {
+				let b = c.blocks.last_mut().unwrap();
+				b.iteration += 1;
+				if b.iteration < b.count {
+					c.next = b.start;
+				} else {
+					let b = c.blocks.pop().unwrap();
+					c.constants = b.constants;
+					if let Some((_, next)) = b.placement {
+						c.next = next;
+					}
+				}
+				InterpretorState::Initial
+			},
+			Token::EndOfFile if c.blocks.is_empty() => InterpretorState::Done,
+			_ => {
+				let mut expected = vec![
+					Token::Unit,
+					Token::Let(String::new()),
+					Token::Metadata(Field::Title),
+					Token::Metadata(Field::Author),
+					Token::Metadata(Field::Difficulty),
+					Token::Metadata(Field::Music),
+					Token::Metadata(Field::Background)
+				];
+				expected.extend(ObjectKind::KEYWORDS.iter().map(|k| Token::Kind(ObjectKind::placeholder(k))));
+				expected.extend([Token::Repeat, Token::Prefab(String::new()), Token::Place(String::new())]);
+				expected.push(if c.blocks.is_empty() { Token::EndOfFile } else { Token::BlockEnd });
+
+				// A missing end of block is not the failure of an iteration
+				if let Token::EndOfFile = t {
+					c.blocks.clear();
+				}
+				return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected});
+			}
+		})
+	}
+
+	/// Index of the token right after the end of the block starting at `start`
+	fn skip_block(tokens: &[(Token, Span)], start: usize) -> Result<usize, InterpretorError> {
+		let mut depth = 1;
+		for (i, (t, _)) in tokens.iter().enumerate().skip(start) {
+			match t {
+				Token::BlockStart => depth += 1,
+				Token::BlockEnd if depth == 1 => return Ok(i + 1),
+				Token::BlockEnd => depth -= 1,
+				_ => {}
+			}
+		}
+
+		let (t, span) = tokens[tokens.len() - 1].clone();
+		Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::BlockEnd]})
+	}
+
+	/// Complete, deterministic, simple automaton.
+	fn transition(c: &mut Context, tokens: &[(Token, Span)], state: InterpretorState, t: Token, span: Span) -> Result<InterpretorState, InterpretorError> {
+		let evaluate = |e: &Expression, constants: &HashMap<String, f32>| e.evaluate(constants).map_err(|e| InterpretorError::Expression(e, span));
+		let unit = c.unit;
+		// Objects in blocks are moved by each enclosing loop and prefab
+		let offset: Vec2 = c.blocks.iter().map(|b| b.origin + b.step * b.iteration as f32).sum();
+
+		Ok(match state {
+			InterpretorState::Initial => Self::statement(c, t, span)?,
+			InterpretorState::UnitDeclaration => match t {
+				Token::Is => InterpretorState::UnitDefinition,
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Is]
+				})
+			},
+			InterpretorState::UnitDefinition => match t {
+				Token::Vector(x, y) => {
+					c.unit = Vec2::new(evaluate(&x, &c.constants)?, evaluate(&y, &c.constants)?);
+					c.world.unit = c.unit;
+					InterpretorState::Initial
 				},
-				InterpretorState::UnitDeclaration => match t {
-					Token::Is => InterpretorState::UnitDefinition,
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![Token::Is]
-					})
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Vector(0.0.into(), 0.0.into())]
+				})
+			},
+			InterpretorState::BindingDefinition(name) => match t {
+				Token::Scalar(_, Unit::Pixel) => return Err(InterpretorError::UnitInBinding(name, span)),
+				Token::Scalar(x, Unit::Default) => {
+					let value = evaluate(&x, &c.constants)?;
+					c.constants.insert(name, value);
+					InterpretorState::Initial
 				},
-				InterpretorState::UnitDefinition => match t {
-					Token::Vector(x, y) => {
-						unit.x = x as f32;
-						unit.y = y as f32;
-						InterpretorState::Initial
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Scalar(0.0.into(), Unit::Default)]
+				})
+			},
+			InterpretorState::MetadataDeclaration(field) => match t {
+				Token::Is => InterpretorState::MetadataDefinition(field),
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Is]
+				})
+			},
+			InterpretorState::MetadataDefinition(field) => {
+				let metadata = &mut c.world.metadata;
+				match (field, t) {
+					(Field::Title, Token::Text(x)) => metadata.title = Some(x),
+					(Field::Author, Token::Text(x)) => metadata.author = Some(x),
+					(Field::Music, Token::Text(x)) => metadata.music = Some(x),
+					(Field::Background, Token::Text(x)) => match Metadata::parse_color(&x) {
+						Some(color) => metadata.background = color,
+						None => return Err(InterpretorError::InvalidMetadata(field, x, span))
+					},
+					(Field::Difficulty, Token::Scalar(x, Unit::Default)) => {
+						let difficulty = evaluate(&x, &c.constants)?;
+						if difficulty < 0. || difficulty.fract() != 0. {
+							return Err(InterpretorError::InvalidMetadata(field, difficulty.to_string(), span));
+						}
+						metadata.difficulty = Some(difficulty as u32);
 					},
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![Token::Is]
+					(Field::Difficulty, t) => return Err(InterpretorError::Unexpected {
+						unexpected: Box::new(t),
+						span,
+						expected: vec![Token::Scalar(0.0.into(), Unit::Default)]
+					}),
+					(_, t) => return Err(InterpretorError::Unexpected {
+						unexpected: Box::new(t),
+						span,
+						expected: vec![Token::Text(String::new())]
 					})
+				}
+				InterpretorState::Initial
+			},
+			InterpretorState::ObjectDeclaration(obj) => match t {
+				Token::At => InterpretorState::PositionDefinition(obj.size(unit)),
+				Token::OfSize => InterpretorState::SizeDefinition(obj.size(unit)),
+				Token::WithIS => InterpretorState::InitialSpeedDefinition(obj),
+				Token::WithGravity => InterpretorState::GravityDefinition(obj),
+				// Paths start where the platform is
+				Token::MovingTo if matches!(obj.kind, ObjectKind::Platform(_)) => {
+					let mut obj = obj;
+					if let ObjectKind::Platform(path) = &mut obj.kind {
+						if path.waypoints.is_empty() {
+							path.waypoints.push(obj.position);
+						}
+					}
+					InterpretorState::WaypointDefinition(obj)
 				},
-				InterpretorState::ObjectDeclaration(obj) => match t {
-					Token::At => InterpretorState::PositionDefinition(obj.size(unit)),
-					Token::OfSize => InterpretorState::SizeDefinition(obj.size(unit)),
-					Token::WithIS => InterpretorState::InitialSpeedDefinition(obj),
-					Token::Kind(k) => {
-						r.add_object(obj);
-						InterpretorState::ObjectDeclaration(Object::from(k))
-					},
-					Token::EndOfFile => {
-						r.add_object(obj);
-						InterpretorState::Done
+				Token::WithSpeed if matches!(obj.kind, ObjectKind::SpeedPortal(_)) => InterpretorState::PortalSpeedDefinition(obj),
+				Token::Looping if matches!(obj.kind, ObjectKind::Platform(_)) => {
+					let mut obj = obj;
+					if let ObjectKind::Platform(path) = &mut obj.kind {
+						path.looping = true;
 					}
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![
-							Token::At,
-							Token::OfSize,
-							Token::WithIS
-						]
-					})
+					InterpretorState::ObjectDeclaration(obj)
 				},
-				InterpretorState::PositionDefinition(obj) => match t {
-					Token::Vector(x, y) => InterpretorState::ObjectDeclaration(obj.position(Vec2::new(x as f32 * unit.x, y as f32 * unit.y))),
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![
-							Token::Vector(0, 0)
-						]
-					}) 
+				Token::Unit | Token::Let(_) | Token::Metadata(_) | Token::Kind(_) | Token::Repeat | Token::Prefab(_) | Token::Place(_) | Token::BlockEnd | Token::EndOfFile => {
+					c.world.add_object(obj);
+					Self::statement(c, t, span)?
 				},
-				InterpretorState::SizeDefinition(obj) => match t {
-					Token::Vector(x, y) => InterpretorState::ObjectDeclaration(obj.size(Vec2::new(x as f32 * unit.x, y as f32 * unit.y))),
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![
-							Token::Vector(0, 0)
-						]
-					}) 
+				_ => {
+					let mut expected = vec![
+						Token::At,
+						Token::OfSize,
+						Token::WithIS,
+						Token::WithGravity
+					];
+					match obj.kind {
+						ObjectKind::Platform(_) => expected.extend([Token::MovingTo, Token::Looping]),
+						ObjectKind::SpeedPortal(_) => expected.push(Token::WithSpeed),
+						_ => {}
+					}
+					return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected});
+				}
+			},
+			InterpretorState::WaypointDefinition(mut obj) => match t {
+				Token::Vector(x, y) => {
+					let waypoint = Vec2::new(evaluate(&x, &c.constants)? * unit.x, evaluate(&y, &c.constants)? * unit.y) + offset;
+					if let ObjectKind::Platform(path) = &mut obj.kind {
+						path.waypoints.push(waypoint);
+					}
+					InterpretorState::PathContinuation(obj)
 				},
-				InterpretorState::InitialSpeedDefinition(obj) => match t {
-					Token::Scalar(x, u) => InterpretorState::ObjectDeclaration(obj.speed(Vec2::new(x as f32 * if let Unit::Default = u{ unit.x } else {1.}, 0.))),
-					_ => return Err(InterpretorError {
-						unexpected: t,
-						expected: vec![
-							Token::Scalar(0, Unit::Default),
-							Token::Scalar(0, Unit::Pixel)
-						]
-					}) 
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Vector(0.0.into(), 0.0.into())]
+				})
+			},
+			InterpretorState::PathContinuation(obj) => match t {
+				Token::Then => InterpretorState::WaypointDefinition(obj),
+				Token::At => InterpretorState::PathSpeedDefinition(obj),
+				_ => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::Then, Token::At]})
+			},
+			InterpretorState::PathSpeedDefinition(mut obj) => match t {
+				Token::Scalar(x, u) => {
+					let speed = evaluate(&x, &c.constants)? * if let Unit::Default = u { unit.x } else { 1. };
+					// Platforms would never reach their next waypoint
+					if speed <= 0. || !speed.is_finite() {
+						return Err(InterpretorError::InvalidSpeed(speed, span));
+					}
+					if let ObjectKind::Platform(path) = &mut obj.kind {
+						path.speed = speed;
+					}
+					InterpretorState::ObjectDeclaration(obj)
 				},
-				InterpretorState::Done => return Err(InterpretorError {unexpected: t, expected: vec![Token::EndOfFile]})
-			};
-		}
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![
+						Token::Scalar(0.0.into(), Unit::Default),
+						Token::Scalar(0.0.into(), Unit::Pixel)
+					]
+				})
+			},
+			InterpretorState::PositionDefinition(obj) => match t {
+				Token::Vector(x, y) => InterpretorState::ObjectDeclaration(obj.position(Vec2::new(evaluate(&x, &c.constants)? * unit.x, evaluate(&y, &c.constants)? * unit.y) + offset)),
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![
+						Token::Vector(0.0.into(), 0.0.into())
+					]
+				}) 
+			},
+			InterpretorState::SizeDefinition(obj) => match t {
+				Token::Vector(x, y) => InterpretorState::ObjectDeclaration(obj.size(Vec2::new(evaluate(&x, &c.constants)? * unit.x, evaluate(&y, &c.constants)? * unit.y))),
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![
+						Token::Vector(0.0.into(), 0.0.into())
+					]
+				}) 
+			},
+			InterpretorState::InitialSpeedDefinition(obj) => match t {
+				Token::Scalar(x, u) => InterpretorState::ObjectDeclaration(obj.speed(Vec2::new(evaluate(&x, &c.constants)? * if let Unit::Default = u{ unit.x } else {1.}, 0.))),
+				Token::Vector(x, y) => InterpretorState::ObjectDeclaration(obj.speed(Vec2::new(evaluate(&x, &c.constants)? * unit.x, evaluate(&y, &c.constants)? * unit.y))),
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![
+						Token::Scalar(0.0.into(), Unit::Default),
+						Token::Scalar(0.0.into(), Unit::Pixel),
+						Token::Vector(0.0.into(), 0.0.into())
+					]
+				}) 
+			},
+			InterpretorState::GravityDefinition(obj) => match t {
+				Token::Scalar(x, u) => InterpretorState::ObjectDeclaration(obj.gravity(evaluate(&x, &c.constants)? * if let Unit::Default = u{ unit.y } else {1.})),
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![
+						Token::Scalar(0.0.into(), Unit::Default),
+						Token::Scalar(0.0.into(), Unit::Pixel)
+					]
+				}) 
+			},
+			InterpretorState::PortalSpeedDefinition(obj) => match t {
+				Token::Scalar(x, u) => {
+					let speed = evaluate(&x, &c.constants)? * if let Unit::Default = u { unit.x } else { 1. };
+					// Jumps are computed for players running forward
+					if speed <= 0. || !speed.is_finite() {
+						return Err(InterpretorError::InvalidSpeed(speed, span));
+					}
+					InterpretorState::ObjectDeclaration(obj.kind(ObjectKind::SpeedPortal(speed)))
+				},
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![
+						Token::Scalar(0.0.into(), Unit::Default),
+						Token::Scalar(0.0.into(), Unit::Pixel)
+					]
+				})
+			},
+			InterpretorState::RepeatCount => match t {
+				Token::Scalar(x, Unit::Default) => {
+					let count = evaluate(&x, &c.constants)?;
+					if count < 0. || count.fract() != 0. {
+						return Err(InterpretorError::InvalidCount(count, span));
+					}
+					// Nested loops count once per iteration of the enclosing ones
+					c.iterations = c.iterations.saturating_add(count as usize);
+					if c.iterations > Self::MAX_ITERATIONS {
+						return Err(InterpretorError::TooManyIterations(Self::MAX_ITERATIONS, span));
+					}
+					InterpretorState::RepeatTimes(count as usize)
+				},
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Scalar(0.0.into(), Unit::Default)]
+				})
+			},
+			InterpretorState::RepeatTimes(count) => match t {
+				Token::Times => InterpretorState::RepeatStep(count),
+				_ => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::Times]})
+			},
+			InterpretorState::RepeatStep(count) => match t {
+				Token::Step => InterpretorState::RepeatOffset(count),
+				_ => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::Step]})
+			},
+			InterpretorState::RepeatOffset(count) => match t {
+				Token::Vector(x, y) => InterpretorState::RepeatBlock(count, Vec2::new(evaluate(&x, &c.constants)? * unit.x, evaluate(&y, &c.constants)? * unit.y)),
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Vector(0.0.into(), 0.0.into())]
+				})
+			},
+			InterpretorState::RepeatBlock(count, step) => match t {
+				Token::BlockStart if count == 0 => {
+					c.next = Self::skip_block(tokens, c.next)?;
+					InterpretorState::Initial
+				},
+				Token::BlockStart => {
+					c.blocks.push(Block {
+						start: c.next,
+						count,
+						iteration: 0,
+						step,
+						origin: Vec2::ZERO,
+						placement: None,
+						line: span.line,
+						constants: c.constants.clone()
+					});
+					InterpretorState::Initial
+				},
+				_ => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::BlockStart]})
+			},
+			InterpretorState::PrefabDefinition(name) => match t {
+				// The block is only read when placed
+				Token::BlockStart => {
+					c.prefabs.insert(name, c.next);
+					c.next = Self::skip_block(tokens, c.next)?;
+					InterpretorState::Initial
+				},
+				_ => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::BlockStart]})
+			},
+			InterpretorState::PlaceAt(name, place) => match t {
+				Token::At => InterpretorState::PlacePosition(name, place),
+				_ => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::At]})
+			},
+			InterpretorState::PlacePosition(name, place) => match t {
+				Token::Vector(x, y) => {
+					let position = Vec2::new(evaluate(&x, &c.constants)? * unit.x, evaluate(&y, &c.constants)? * unit.y);
+					let start = *c.prefabs.get(&name).ok_or(InterpretorError::UnknownPrefab(name.clone(), place))?;
+					if c.blocks.iter().any(|b| b.placement.as_ref().is_some_and(|(p, _)| *p == name)) {
+						return Err(InterpretorError::RecursivePrefab(name, place));
+					}
 
-		Ok(r)
+					c.blocks.push(Block {
+						start,
+						count: 1,
+						iteration: 0,
+						step: Vec2::ZERO,
+						origin: position,
+						placement: Some((name, c.next)),
+						line: place.line,
+						constants: c.constants.clone()
+					});
+					c.next = start;
+					InterpretorState::Initial
+				},
+				_ => return Err(InterpretorError::Unexpected {
+					unexpected: Box::new(t),
+					span,
+					expected: vec![Token::Vector(0.0.into(), 0.0.into())]
+				})
+			},
+			InterpretorState::Done => return Err(InterpretorError::Unexpected {unexpected: Box::new(t), span, expected: vec![Token::EndOfFile]})
+		})
 	}
-}
\ No newline at end of file
+}
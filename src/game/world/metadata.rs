@@ -0,0 +1,98 @@
+//! Level header: how the level is presented rather than what it contains
+
+use std::fmt;
+
+use macroquad::prelude::*;
+
+/// Metadata fields, each set by a `<Field> is <value>` statement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+	Title,
+	Author,
+	Difficulty,
+	Music,
+	Background
+}
+
+impl Field {
+	pub const ALL: [Field; 5] = [Field::Title, Field::Author, Field::Difficulty, Field::Music, Field::Background];
+
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			Self::Title => "Title",
+			Self::Author => "Author",
+			Self::Difficulty => "Difficulty",
+			Self::Music => "Music",
+			Self::Background => "Background"
+		}
+	}
+
+	pub fn from_keyword(s: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|f| f.keyword() == s)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+	pub title: Option<String>,
+	pub author: Option<String>,
+	pub difficulty: Option<u32>,
+	/// Sound file in `res/sounds`, looped while playing
+	pub music: Option<String>,
+	pub background: Color
+}
+
+impl Default for Metadata {
+	fn default() -> Self {
+		Self {
+			title: None,
+			author: None,
+			difficulty: None,
+			music: None,
+			background: BLACK
+		}
+	}
+}
+
+impl Metadata {
+	/// Title of the level, its file name without extension if it has none
+	pub fn title_or(&self, level: &str) -> String {
+		self.title
+			.clone()
+			.unwrap_or_else(|| level.split('.').next().unwrap_or(level).to_owned())
+	}
+
+	/// Parses `#rrggbb` colors
+	pub fn parse_color(s: &str) -> Option<Color> {
+		let hex = s.strip_prefix('#').filter(|h| h.len() == 6)?;
+		let rgb = u32::from_str_radix(hex, 16).ok()?;
+		Some(Color::from_rgba((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255))
+	}
+
+	pub fn color_to_string(c: Color) -> String {
+		let byte = |x: f32| (x * 255.).round() as u8;
+		format!("#{:02x}{:02x}{:02x}", byte(c.r), byte(c.g), byte(c.b))
+	}
+}
+
+/// Writes the header in the world definition language, only fields that are set
+impl fmt::Display for Metadata {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if let Some(title) = &self.title {
+			writeln!(f, "Title is \"{title}\"")?;
+		}
+		if let Some(author) = &self.author {
+			writeln!(f, "Author is \"{author}\"")?;
+		}
+		if let Some(difficulty) = self.difficulty {
+			writeln!(f, "Difficulty is {difficulty}")?;
+		}
+		if let Some(music) = &self.music {
+			writeln!(f, "Music is \"{music}\"")?;
+		}
+		if self.background != BLACK {
+			writeln!(f, "Background is \"{}\"", Self::color_to_string(self.background))?;
+		}
+		Ok(())
+	}
+}
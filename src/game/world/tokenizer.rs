@@ -1,34 +1,46 @@
 //! Lexer for the world definition language
 
+use std::fmt;
+
 use super::super::resource_manager::*;
-use macroquad::prelude::*;
 use super::super::object::ObjectKind;
+use super::diagnostic::*;
+use super::expression::Expression;
+use super::metadata::Field;
 
 #[derive(Debug)]
 pub enum TokenizerError {
-	UnknownToken (String),
-	UnexpectedEOF,
-	UnknownUnit (String)
+	UnknownToken (String, Span),
+	UnexpectedEOF (Span),
+	UnknownUnit (String, Span)
 }
 
+impl Diagnostic for TokenizerError {
+	fn span(&self) -> Span {
+		match self {
+			Self::UnknownToken(_, span)
+			| Self::UnexpectedEOF(span)
+			| Self::UnknownUnit(_, span) => *span
+		}
+	}
 
-enum VectorParsingState {
-	Initial,
-	X,
-	Y,
-	Bidimensional,
-	ScalarDefault,
-	ScalarUnit,
-	Done
+	fn message(&self) -> String {
+		match self {
+			Self::UnknownToken(s, _) => format!("unknown token `{s}`"),
+			Self::UnexpectedEOF(_) => "unexpected end of literal".to_string(),
+			Self::UnknownUnit(s, _) => format!("unknown unit `{s}`, expected `px` or nothing")
+		}
+	}
 }
 
-#[derive(Debug)]
+
+#[derive(Debug, Clone)]
 pub enum Unit {
 	Default,
 	Pixel
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Token {
 	Unit,
 	Kind (ObjectKind),
@@ -36,185 +48,311 @@ pub enum Token {
 	At,
 	OfSize,
 	WithIS,
-	Vector (i32, i32),
-	Scalar (i32, Unit),
+	WithGravity,
+	WithSpeed,
+	MovingTo,
+	Then,
+	Looping,
+	Let (String),
+	Metadata (Field),
+	Include (String),
+	Prefab (String),
+	Place (String),
+	Repeat,
+	Times,
+	Step,
+	BlockStart,
+	BlockEnd,
+	Text (String),
+	Vector (Expression, Expression),
+	Scalar (Expression, Unit),
 	EndOfFile
 }
+
+/// Literals are displayed by category, as they appear in "expected" lists,
+/// except lone names which are more likely misspelled keywords
+impl fmt::Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Scalar(Expression::Constant(name), Unit::Default) => write!(f, "`{name}`"),
+			Self::Unit => write!(f, "`Unit`"),
+			Self::Let(..) => write!(f, "`let`"),
+			Self::Metadata(field) => write!(f, "`{}`", field.keyword()),
+			Self::Include(..) => write!(f, "`include`"),
+			Self::Prefab(..) => write!(f, "`prefab`"),
+			Self::Place(..) => write!(f, "`place`"),
+			Self::Repeat => write!(f, "`repeat`"),
+			Self::Times => write!(f, "`times`"),
+			Self::Step => write!(f, "`step`"),
+			Self::BlockStart => write!(f, "`{{`"),
+			Self::BlockEnd => write!(f, "`}}`"),
+			Self::Kind(k) => write!(f, "`{k}`"),
+			Self::Is => write!(f, "`is`"),
+			Self::At => write!(f, "`at`"),
+			Self::OfSize => write!(f, "`of size`"),
+			Self::WithIS => write!(f, "`with initial speed of`"),
+			Self::WithGravity => write!(f, "`with gravity of`"),
+			Self::WithSpeed => write!(f, "`with speed of`"),
+			Self::MovingTo => write!(f, "`moving to`"),
+			Self::Then => write!(f, "`then`"),
+			Self::Looping => write!(f, "`looping`"),
+			Self::Text(..) => write!(f, "text"),
+			Self::Vector(..) => write!(f, "vector"),
+			Self::Scalar(..) => write!(f, "scalar"),
+			Self::EndOfFile => write!(f, "end of file")
+		}
+	}
+}
+
 impl Token {
-	async fn from(s: &str, rm: &mut ResourceManager) -> Result::<Self, TokenizerError> {
+	async fn from(s: &str, span: Span, rm: &mut ResourceManager) -> Result::<Self, TokenizerError> {
+		if ObjectKind::KEYWORDS.contains(&s) {
+			return Ok(Token::Kind(ObjectKind::from((s, rm)).await));
+		}
+		match Self::keyword(s) {
+			Some(t) => Ok(t),
+			None if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') => Ok(Token::Text(s[1..s.len() - 1].to_owned())),
+			None => Self::literal(s, span)
+		}
+	}
+
+	/// Keywords other than object kinds, once collapsed
+	fn keyword(s: &str) -> Option<Self> {
 		match s {
-			"Unit" => Ok(Token::Unit),
-			"Spike" | "Player" | "Wall" => Ok(Token::Kind(ObjectKind::from((s, rm)).await)),
-			"is" => Ok(Token::Is),
-			"at" => Ok(Token::At),
-			"ofsize" => Ok(Token::OfSize),
-			"wiso" => Ok(Token::WithIS),
-			_ => {
-				// Parsing vector and scalar litterals
-
-			  	let mut state = VectorParsingState::Initial;
-
-			  	let word = format!("{s}\n");
-			  	let mut buffer = String::new();
-			  	let mut parsed = (false, 0, 0);
-
-			  	for c in word.chars() {
-			  		buffer.push(c);
-			  		
-			  		// Complete, deterministic, simple automaton
-			  		state = match state {
-			  			VectorParsingState::Initial => match c {
-			  				'(' => {
-			  					buffer.clear();
-			  					parsed.0 = true;
-			  					VectorParsingState::X
-			  				},
-			  				'0' ..= '9' => VectorParsingState::ScalarDefault,
-			  				 _ => return Err(TokenizerError::UnknownToken(s.to_string()))
-			  			},
-			  			VectorParsingState::X => match c {
-			  				'0' ..= '9' => VectorParsingState::X,
-			  				',' => {
-			  					buffer.pop().unwrap();
-			  					parsed.1 = buffer.parse::<i32>().unwrap();
-			  					buffer.clear();
-			  					VectorParsingState::Y
-			  				},
-			  				 _ => return Err(TokenizerError::UnknownToken(s.to_string()))
-			  			},
-			  			VectorParsingState::Y => match c {
-			  				'0' ..= '9' => VectorParsingState::Y,
-			  				')' => {
-			  					buffer.pop().unwrap();
-			  					parsed.2 = buffer.parse::<i32>().unwrap();
-			  					buffer.clear();
-			  					VectorParsingState::Bidimensional
-			  				},
-			  				 _ => return Err(TokenizerError::UnknownToken(s.to_string()))
-			  			},
-			  			VectorParsingState::Bidimensional => match c {
-			  				'\n' => VectorParsingState::Done,
-			  				_ => return Err(TokenizerError::UnknownToken(s.to_string())),
-			  			},
-			  			VectorParsingState::ScalarDefault => match c {
-			  				'0' ..= '9' => VectorParsingState::ScalarDefault,
-			  				'\n' => {
-			  					buffer.pop().unwrap();
-			  					parsed.1 = buffer.parse::<i32>().unwrap();
-			  					buffer.clear();
-			  					VectorParsingState::Done
-			  				},
-			  				'p' => {
-			  					buffer.pop().unwrap();
-			  					parsed.1 = buffer.parse::<i32>().unwrap();
-			  					buffer.clear();
-			  					buffer.push('p');
-			  					VectorParsingState::ScalarUnit
-			  				},
-			  				 _ => return Err(TokenizerError::UnknownToken(s.to_string()))
-			  			},
-			  			VectorParsingState::ScalarUnit => {
-			  				if c == '\n' {
-			  					buffer.pop().unwrap();
-			  					// unit = buffer.clone();
-			  					// buffer.clear();
-			  					VectorParsingState::Done
-			  				} else {
-			  					VectorParsingState::ScalarUnit
-			  				}
-			  			},
-			  			VectorParsingState::Done => return Err(TokenizerError::UnknownToken(s.to_string()))
-			  		}
-			  	}
-
-			  	if let VectorParsingState::Done = state {
-			  		if parsed.0 {
-			  			Ok(Self::Vector (parsed.1, parsed.2))
-			  		} else {
-			  			Ok(Self::Scalar (
-			  				parsed.1,
-			  				match &buffer[..] {
-			  					"px" => Unit::Pixel,
-			  					""   => Unit::Default,
-			  					_ =>  return Err(TokenizerError::UnknownUnit(buffer))
-			  				}
-			  			))
-			  		}
-			  	} else {
-			  		Err(TokenizerError::UnexpectedEOF)
-			  	}
+			"Unit" => Some(Token::Unit),
+			"is" => Some(Token::Is),
+			"at" => Some(Token::At),
+			"ofsize" => Some(Token::OfSize),
+			"wiso" => Some(Token::WithIS),
+			"wgo" => Some(Token::WithGravity),
+			"wso" => Some(Token::WithSpeed),
+			"movingto" => Some(Token::MovingTo),
+			"then" => Some(Token::Then),
+			"looping" => Some(Token::Looping),
+			"repeat" => Some(Token::Repeat),
+			"times" => Some(Token::Times),
+			"step" => Some(Token::Step),
+			"{" => Some(Token::BlockStart),
+			"}" => Some(Token::BlockEnd),
+			_ => Field::from_keyword(s).map(Token::Metadata)
+		}
+	}
+
+	/// Parses vector and scalar litterals, whose components are expressions
+	fn literal(s: &str, span: Span) -> Result::<Self, TokenizerError> {
+		let unknown = || TokenizerError::UnknownToken(s.to_string(), span);
+		let expression = |e: &str| Expression::parse(e).ok_or_else(unknown);
+
+		// Depth of parentheses, a vector is parenthesized and has a comma at depth 1
+		let mut depth = 0;
+		let mut comma = None;
+		for (i, c) in s.char_indices() {
+			match c {
+				'(' => depth += 1,
+				')' if depth == 0 => return Err(unknown()),
+				')' => depth -= 1,
+				',' if depth == 1 && comma.is_none() => comma = Some(i),
+				',' => return Err(unknown()),
+				_ => {}
+			}
+		}
+		if depth > 0 {
+			return Err(TokenizerError::UnexpectedEOF(span));
+		}
+
+		if let Some(comma) = comma {
+			return match (s.starts_with('('), s.ends_with(')')) {
+				(true, true) => Ok(Self::Vector(expression(&s[1..comma])?, expression(&s[comma + 1..s.len() - 1])?)),
+				_ => Err(unknown())
+			};
+		}
+
+		// Scalars may end with a unit
+		let number = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+		let unit = &s[number.len()..];
+		if unit == "px" {
+			if let Some(e) = Expression::parse(number) {
+				return Ok(Self::Scalar(e, Unit::Pixel));
 			}
 		}
+		match Expression::parse(s) {
+			Some(e) => Ok(Self::Scalar(e, Unit::Default)),
+			None if !unit.is_empty() && Expression::parse(number).is_some() => Err(TokenizerError::UnknownUnit(unit.to_string(), span)),
+			None => Err(unknown())
+		}
 	}
 }
 
 pub struct Tokenizer;
 impl Tokenizer {
 
-	pub async fn tokenize(source: &str, rm: &mut ResourceManager) -> Result::<Vec::<Token>, TokenizerError> {
-		let mut r = Vec::<Token>::new();
-
-		// Removing blank characters in litterals
-		let mut s = String::new();
-		let mut level = 0;
-		for c in source.chars() {
-			if c == ')' { level -= 1 }
-			if c == '(' { level += 1 }
-			if level != 0 && c != ' ' || level == 0 {
-				s.push(c);
-			}
-		}
-
-		// Splitting words and removing commentaries
-		let mut binding = s
-			.split("\n")
-			.filter(|x| x.len() > 0 && &x[0..1] != "#")
-			.map(|x| x.split(" "))
-			.flatten()
-			.collect::<Vec::<&str>>();
+	pub async fn tokenize(source: &str, rm: &mut ResourceManager) -> Result::<Vec::<(Token, Span)>, TokenizerError> {
+		let mut r = Vec::<(Token, Span)>::new();
 
-		// Removing empty words
-		binding = binding
-			.into_iter()
-			.filter(|x| x.len() != 0)
-			.collect();
+		let mut binding = Self::split(source);
 
 		// Simplifying
 		binding = Self::collapse(binding, &["with", "initial", "speed", "of"], "wiso");
+		binding = Self::collapse(binding, &["with", "gravity", "of"], "wgo");
+		binding = Self::collapse(binding, &["with", "speed", "of"], "wso");
 		binding = Self::collapse(binding, &["of", "size"], "ofsize");
+		binding = Self::collapse(binding, &["moving", "to"], "movingto");
+		binding = Self::join(binding);
 
 		// Translating in tokens
-		for word in binding {
-			r.push(Token::from(word, rm).await?);
+		let mut words = binding.into_iter();
+		while let Some((word, span)) = words.next() {
+			match &word[..] {
+				"include" => {
+					// `include "file"`
+					let (file, file_span) = words.next().ok_or(TokenizerError::UnexpectedEOF(span))?;
+					match file.strip_prefix('"').and_then(|f| f.strip_suffix('"')) {
+						Some(f) if !f.is_empty() => r.push((Token::Include(f.to_owned()), span.to(file_span))),
+						_ => return Err(TokenizerError::UnknownToken(file, file_span))
+					}
+				},
+				"prefab" | "place" => {
+					// `prefab Name` or `place Name`
+					let (name, name_span) = words.next().ok_or(TokenizerError::UnexpectedEOF(span))?;
+					if !Expression::is_name(&name) {
+						return Err(TokenizerError::UnknownToken(name, name_span));
+					}
+					let token = if word == "prefab" { Token::Prefab(name) } else { Token::Place(name) };
+					r.push((token, span.to(name_span)));
+				},
+				"let" => {
+					// `let name = expression`, where expression may be a lone constant
+					let (name, name_span) = words.next().ok_or(TokenizerError::UnexpectedEOF(span))?;
+					if !Expression::is_name(&name) {
+						return Err(TokenizerError::UnknownToken(name, name_span));
+					}
+					match words.next() {
+						Some((equals, _)) if equals == "=" => {},
+						Some((other, other_span)) => return Err(TokenizerError::UnknownToken(other, other_span)),
+						None => return Err(TokenizerError::UnexpectedEOF(name_span))
+					}
+					let (value, value_span) = words.next().ok_or(TokenizerError::UnexpectedEOF(name_span))?;
+
+					r.push((Token::Let(name), span.to(name_span)));
+					r.push((Token::literal(&value, value_span)?, value_span));
+				},
+				_ => r.push((Token::from(&word, span, rm).await?, span))
+			}
 		}
 
-		r.push(Token::EndOfFile);
+		// End of file is located right after the last character of the source
+		let last_line = source.lines().count().max(1);
+		let last_column = source.lines().last().map(|l| l.chars().count()).unwrap_or(0) + 1;
+		r.push((Token::EndOfFile, Span::new(last_line, last_column, 1)));
 
 		Ok(r)
 	}
 
-	/// Removes any target sequence and put replacement instead in source
-	fn collapse<'a>(source: Vec<&'a str>, target: &[&str], replacement: &'a str) -> Vec::<&'a str> {
-		let mut r = Vec::<&str>::new();
-		let mut i = 0;
-		while i < source.len() {
-			if i < source.len() - target.len() {
-				let mut eq = true;
-				for j in i..(i+target.len()) {
-					if source[j] != target[j-i] {
-						eq = false;
+	/// Splits source into words, keeping track of where they come from.
+	/// Commentaries are removed and blank characters in litterals are ignored,
+	/// as in the value of bindings, but kept in quoted text.
+	fn split(source: &str) -> Vec::<(String, Span)> {
+		let mut r = Vec::<(String, Span)>::new();
+
+		for (l, line) in source.lines().enumerate() {
+			if line.trim_start().starts_with('#') {
+				continue;
+			}
+
+			let binding = line.trim_start().starts_with("let ");
+			let mut value = false;
+
+			let mut word = String::new();
+			let mut start = 0;
+			let mut level = 0;
+			let mut quoted = false;
+			for (c, character) in line.chars().enumerate() {
+				if character == '"' { quoted = !quoted }
+				if quoted {
+					if word.is_empty() {
+						start = c;
 					}
+					word.push(character);
+					continue;
 				}
-				if eq {
-					r.push(replacement);
-					i += target.len() - 1;
+				if character == ')' { level -= 1 }
+				if character == '(' { level += 1 }
+
+				if binding && !value && level == 0 && character == '=' {
+					if !word.is_empty() {
+						r.push((std::mem::take(&mut word), Span::new(l + 1, start + 1, c - start)));
+					}
+					r.push(("=".to_string(), Span::new(l + 1, c + 1, 1)));
+					value = true;
+					continue;
+				}
+
+				if character.is_whitespace() {
+					if level == 0 && !value && !word.is_empty() {
+						r.push((std::mem::take(&mut word), Span::new(l + 1, start + 1, c - start)));
+					}
 				} else {
-					r.push(source[i]);
+					if word.is_empty() {
+						start = c;
+					}
+					word.push(character);
 				}
+			}
+
+			if !word.is_empty() {
+				let length = line.chars().count() - start;
+				r.push((word, Span::new(l + 1, start + 1, length)));
+			}
+		}
+
+		r
+	}
+
+	/// Removes any target sequence and put replacement instead in source
+	fn collapse(source: Vec<(String, Span)>, target: &[&str], replacement: &str) -> Vec::<(String, Span)> {
+		let mut r = Vec::<(String, Span)>::new();
+		let mut i = 0;
+		while i < source.len() {
+			let eq = i + target.len() <= source.len()
+				&& target
+					.iter()
+					.enumerate()
+					.all(|(j, t)| source[i + j].0 == *t);
+
+			if eq {
+				let span = source[i].1.to(source[i + target.len() - 1].1);
+				r.push((replacement.to_string(), span));
+				i += target.len();
 			} else {
-				r.push(source[i]);
+				r.push(source[i].clone());
+				i += 1;
+			}
+		}
+
+		r
+	}
+
+	/// Joins scalar expressions written with blanks around operators, such as
+	/// `gap + 1`, into a single word
+	fn join(source: Vec<(String, Span)>) -> Vec::<(String, Span)> {
+		let operator = |c: char| matches!(c, '+' | '-' | '*' | '/');
+		let operand = |w: &str| !w.starts_with('"')
+			&& !ObjectKind::KEYWORDS.contains(&w)
+			&& Token::keyword(w).is_none()
+			&& !matches!(w, "include" | "prefab" | "place" | "let" | "=");
+
+		let mut r = Vec::<(String, Span)>::new();
+		for (word, span) in source {
+			if let Some((last, last_span)) = r.last_mut() {
+				if last_span.line == span.line && last_span.file == span.file
+					&& operand(last) && operand(&word)
+					&& (last.ends_with(operator) || word.starts_with(operator)) {
+					last.push_str(&word);
+					*last_span = last_span.to(span);
+					continue;
+				}
 			}
-			i+=1;
+			r.push((word, span));
 		}
 
 		r
@@ -0,0 +1,7 @@
+//! Quick and fun runner type game
+
+pub mod application;
+pub mod ui;
+pub mod game;
+pub mod editor;
+pub mod cli;
@@ -1,10 +1,8 @@
-use application::Application;
+use runner::application::Application;
+use runner::cli::*;
+use runner::game::world::World;
 use macroquad::prelude::*;
 
-mod application;
-mod ui;
-mod game;
-
 fn window_conf() -> Conf {
 	Conf {
 		window_width: 800,
@@ -14,9 +12,34 @@ fn window_conf() -> Conf {
 	}
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-	let mut application = Application::new();
+fn main() {
+	let arguments = Arguments::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+		eprintln!("error: {e}\n\n{USAGE}");
+		std::process::exit(2);
+	});
+
+	if let Some(directory) = &arguments.levels_dir {
+		if !std::path::Path::new(directory).is_dir() {
+			eprintln!("error: `{directory}` is not a directory");
+			std::process::exit(2);
+		}
+		World::set_levels_dir(directory);
+	}
+
+	match arguments.command {
+		Command::Play(level, start) => macroquad::Window::from_config(window_conf(), async move {
+			let mut application = Application::new();
+			if let Some(level) = &level {
+				application = application.play(level, start);
+			}
 
-	application.run().await;
-}
\ No newline at end of file
+			application.run().await;
+		}),
+		Command::Check(files) => {
+			if check(&files) > 0 {
+				std::process::exit(1);
+			}
+		},
+		Command::Help => println!("{USAGE}")
+	}
+}
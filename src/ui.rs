@@ -6,6 +6,10 @@ use menu::Menu;
 use widget::*;
 use std::cmp::Ordering;
 
+use crate::game::replay::Replay;
+use crate::game::statistics::Statistics;
+use crate::game::world::World;
+
 mod menu;
 mod widget;
 
@@ -16,6 +20,8 @@ pub struct Ui {
 	current_menu: usize,
 	finished: bool,
 	requested_level: Option<String>,
+	requested_replay: Option<String>,
+	requested_edit: Option<String>,
 	requested_volume: Option<f32>
 }
 
@@ -24,28 +30,6 @@ pub struct Ui {
 impl Default for Ui {
 	
 	fn default() -> Self {
-		
-		let mut level_selection = Menu::new("Play");
-		level_selection = level_selection.add_widget(
-			Box::new(
-				Button::new(Vec2::new(10., 10.), Vec2::new(200., 50.))
-					.title("Main menu")
-					.role(SpecialRole::StateChanger)
-			)
-		);
-		for (i, path) in std::fs::read_dir("res/levels").unwrap().enumerate() {
-			level_selection = level_selection.add_widget(
-				Box::new(
-					Button::new(
-						Vec2::new(300., i as f32 * 55.),
-						Vec2::new(200., 50.)
-					)
-					.title(path.unwrap().path().file_name().unwrap().to_str().unwrap())
-					.role(SpecialRole::StateChanger)
-					.role(SpecialRole::LevelSelector)
-				)
-			);
-		}
 
 		let center = vec2(screen_width(), screen_height())/2.;
 
@@ -64,11 +48,25 @@ impl Default for Ui {
 					))
 					.add_widget(Box::new(
 						Button::new(Vec2::new(100., 250.), Vec2::new(200., 50.))
+							.title("Editor")
+							.role(SpecialRole::StateChanger)
+					))
+					.add_widget(Box::new(
+						Button::new(Vec2::new(100., 325.), Vec2::new(200., 50.))
 							.title("Quit")
 							.role(SpecialRole::StateChanger)
 					))
 			)
-			.add_menu(level_selection)
+			.add_menu(Self::level_selection(&Statistics::default()))
+			.add_menu(Self::editor_selection())
+			.add_menu(
+				Menu::new("Editing")
+					.add_widget(Box::new(
+							Button::new(Vec2::new(10., 10.), Vec2::new(200., 50.))
+								.title("Editor")
+								.role(SpecialRole::StateChanger)
+					))
+			)
 			.add_menu(
 				Menu::new(".lvl")
 					.add_widget(Box::new(
@@ -77,6 +75,22 @@ impl Default for Ui {
 								.role(SpecialRole::StateChanger)
 					))
 			)
+			.add_menu(
+				Menu::new(".replay")
+					.add_widget(Box::new(
+							Button::new(Vec2::new(10., 10.), Vec2::new(200., 50.))
+								.title("Main menu")
+								.role(SpecialRole::StateChanger)
+					))
+			)
+			.add_menu(
+				Menu::new("Error")
+					.add_widget(Box::new(
+							Button::new(Vec2::new(10., 10.), Vec2::new(200., 50.))
+								.title("Play")
+								.role(SpecialRole::StateChanger)
+					))
+			)
 			.add_menu(
 				Menu::new("Options")
 					.add_widget(Box::new(
@@ -108,12 +122,156 @@ impl Default for Ui {
 
 impl Ui {
 
+	/// Names of the level files, sorted
+	fn levels() -> Vec::<String> {
+		let mut levels: Vec::<String> = std::fs::read_dir(World::levels_dir())
+			.unwrap()
+			.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+			.filter(|name| name.ends_with(".lvl"))
+			.collect();
+		levels.sort();
+		levels
+	}
+
+	/// Title of a level, or its file name if it cannot be loaded
+	fn level_title(level: &str) -> String {
+		World::read_metadata(level)
+			.map(|m| m.title_or(level))
+			.unwrap_or_else(|_| level.to_owned())
+	}
+
+	/// Menu listing every level to edit, and a new one
+	fn editor_selection() -> Menu {
+		let mut editor_selection = Menu::new("Editor")
+			.add_widget(Box::new(
+				Button::new(Vec2::new(10., 10.), Vec2::new(200., 50.))
+					.title("Main menu")
+					.role(SpecialRole::StateChanger)
+			));
+
+		let levels = Self::levels();
+		for (i, level) in levels.iter().enumerate() {
+			editor_selection = editor_selection.add_widget(
+				Box::new(
+					Button::new(Vec2::new(230., i as f32 * 55.), Vec2::new(200., 50.))
+						.title(level)
+						.label(&Self::level_title(level))
+						.role(SpecialRole::EditorSelector)
+				)
+			);
+		}
+
+		// First free name for a new level
+		let new_level = (1..)
+			.map(|n| format!("level{n}.lvl"))
+			.find(|name| !levels.contains(name))
+			.unwrap();
+		editor_selection.add_widget(
+			Box::new(
+				Button::new(Vec2::new(10., 75.), Vec2::new(200., 50.))
+					.title(&new_level)
+					.label("New level")
+					.role(SpecialRole::EditorSelector)
+			)
+		)
+	}
+
+	/// Level selection menu, listing every level with its statistics
+	/// and its replay if any
+	fn level_selection(statistics: &Statistics) -> Menu {
+		let mut level_selection = Menu::new("Play");
+		level_selection = level_selection.add_widget(
+			Box::new(
+				Button::new(Vec2::new(10., 10.), Vec2::new(200., 50.))
+					.title("Main menu")
+					.role(SpecialRole::StateChanger)
+			)
+		);
+
+		for (i, level) in Self::levels().iter().enumerate() {
+			let metadata = World::read_metadata(level).ok();
+
+			level_selection = level_selection.add_widget(
+				Box::new(
+					Button::new(
+						Vec2::new(230., i as f32 * 55.),
+						Vec2::new(200., 50.)
+					)
+					.title(level)
+					.label(&metadata.as_ref().map_or_else(|| level.to_owned(), |m| m.title_or(level)))
+					.role(SpecialRole::StateChanger)
+					.role(SpecialRole::LevelSelector)
+				)
+			);
+
+			let replay = Replay::file_name(level);
+			if std::path::Path::new(&format!("{}/{replay}", World::levels_dir())).exists() {
+				level_selection = level_selection.add_widget(
+					Box::new(
+						Button::new(
+							Vec2::new(440., i as f32 * 55.),
+							Vec2::new(150., 50.)
+						)
+						.title(&replay)
+						.label("Replay")
+						.role(SpecialRole::StateChanger)
+						.role(SpecialRole::ReplaySelector)
+					)
+				);
+			}
+
+			let mut details = Vec::<String>::new();
+			if let Some(metadata) = &metadata {
+				let mut header = Vec::<String>::new();
+				if let Some(author) = &metadata.author {
+					header.push(format!("by {author}"));
+				}
+				if let Some(difficulty) = metadata.difficulty {
+					header.push(format!("difficulty {difficulty}"));
+				}
+				if !header.is_empty() {
+					details.push(header.join(", "));
+				}
+			}
+			if let Some(stats) = statistics.level(level) {
+				let best = match stats.best_time {
+					Some(t) => format!("Best {t:.2}s"),
+					None => "Not completed".to_string()
+				};
+				details.push(format!("{best}, {} attempts, {} deaths", stats.attempts, stats.death_count));
+			}
+			if !details.is_empty() {
+				level_selection = level_selection.add_widget(
+					Box::new(
+						Label::new(Vec2::new(600., i as f32 * 55.), &details.join("\n"))
+							.font_size(18.)
+					)
+				);
+			}
+		}
+
+		level_selection
+	}
+
+	/// Rebuilds the menus listing levels, e.g. after a replay was saved
+	pub fn refresh_levels(&mut self, statistics: &Statistics) {
+		for menu in self.menus.iter_mut() {
+			match &menu.id()[..] {
+				"Play" => *menu = Self::level_selection(statistics),
+				"Editor" => *menu = Self::editor_selection(),
+				_ => {}
+			}
+		}
+	}
+
 	pub fn new() -> Self {
 		Self {
 			menus: Vec::<Menu>::new(),
 			current_menu: 0,
 			finished: false,
 			requested_level: None,
+			requested_replay: None,
+			requested_edit: None,
 			requested_volume: None
 		}
 	}
@@ -133,6 +291,8 @@ impl Ui {
 	/// Broadcasts the update call to current menu and manages menu
 	/// switching and communicating user actions to application
 	pub fn update(&mut self) {
+		let mut next_state = None;
+
 		if let Some(menu) = self.menus.get_mut(self.current_menu) {
 			
 			menu.update();
@@ -146,17 +306,7 @@ impl Ui {
 						next_menu = next_menu.chars().skip_while(|x| *x!='.').collect();
 					}
 
-					let next_menu_ids: Vec::<usize> = self.menus
-						.iter()
-						.enumerate()
-						.filter_map(|(i, m)| if m.id() == next_menu { Some(i) } else { None })
-						.collect();
-
-					match next_menu_ids.len().cmp(&1usize) {
-						Ordering::Less => eprintln!("Ui: No menu with id '{next_menu}'."),
-						Ordering::Equal => self.current_menu = next_menu_ids[0],
-						Ordering::Greater => eprintln!("Ui: Multiple menus with id '{next_menu}'.")
-					}
+					next_state = Some(next_menu);
 				}
 				if roles.contains(&SpecialRole::WindowDestroyer) {
 					self.finished = true;
@@ -164,6 +314,13 @@ impl Ui {
 				if roles.contains(&SpecialRole::LevelSelector) {
 					self.requested_level = Some(id.clone());
 				}
+				if roles.contains(&SpecialRole::ReplaySelector) {
+					self.requested_replay = Some(Replay::level_name(&id));
+				}
+				if roles.contains(&SpecialRole::EditorSelector) {
+					self.requested_edit = Some(id.clone());
+					next_state = Some("Editing".to_string());
+				}
 				if roles.contains(&SpecialRole::VolumeChanger) {
 					self.requested_volume = Some(activation);
 				}
@@ -171,6 +328,25 @@ impl Ui {
 		} else {
 			eprintln!("Ui: Menu id larger than menu Vec : {}.", self.current_menu);
 		}
+
+		if let Some(next_menu) = next_state {
+			self.set_state(&next_menu);
+		}
+	}
+
+	/// Switches to the menu identified by `next_menu`
+	pub fn set_state(&mut self, next_menu: &str) {
+		let next_menu_ids: Vec::<usize> = self.menus
+			.iter()
+			.enumerate()
+			.filter_map(|(i, m)| if m.id() == next_menu { Some(i) } else { None })
+			.collect();
+
+		match next_menu_ids.len().cmp(&1usize) {
+			Ordering::Less => eprintln!("Ui: No menu with id '{next_menu}'."),
+			Ordering::Equal => self.current_menu = next_menu_ids[0],
+			Ordering::Greater => eprintln!("Ui: Multiple menus with id '{next_menu}'.")
+		}
 	}
 
 	pub fn state(&self) -> Option<String> {
@@ -183,10 +359,26 @@ impl Ui {
 
 	pub fn is_finished(&self) -> bool { self.finished }
 
+	/// Plays `level` as if it was selected in the level menu
+	pub fn request_level(&mut self, level: &str) {
+		self.requested_level = Some(level.to_owned());
+		self.set_state(".lvl");
+	}
+
 	pub fn get_requested_level(&mut self) -> Option<String> {
 		std::mem::replace(&mut self.requested_level, None)
 	}
 
+	/// Level whose replay should be watched
+	pub fn get_requested_replay(&mut self) -> Option<String> {
+		self.requested_replay.take()
+	}
+
+	/// Level to open in the editor
+	pub fn get_requested_edit(&mut self) -> Option<String> {
+		self.requested_edit.take()
+	}
+
 	pub fn get_requested_volume(&mut self) -> Option<f32> {
 		std::mem::replace(&mut self.requested_volume, None)
 	}
@@ -23,6 +23,8 @@ pub enum SpecialRole {
 	StateChanger,
 	WindowDestroyer,
 	LevelSelector,
+	ReplaySelector,
+	EditorSelector,
 	VolumeChanger
 }
 
@@ -125,10 +127,60 @@ impl Widget for SlideBar {
 	fn get_roles(&self) -> Vec::<SpecialRole> { self.roles.clone() }
 }
 
+/// Static text, never activated
+pub struct Label {
+	position: Vec2,
+	text: String,
+	font_size: f32
+}
+
+impl Label {
+	pub fn new(position: Vec2, text: &str) -> Self {
+		Self {
+			position,
+			text: text.to_owned(),
+			font_size: 20.
+		}
+	}
+
+	pub fn font_size(mut self, x: f32) -> Self {
+		self.font_size = x;
+		self
+	}
+}
+
+impl Widget for Label {
+	fn get_position(&self) -> Vec2 { self.position }
+	fn get_size(&self) -> Vec2 {
+		let lines = self.text.lines().count() as f32;
+		let width = self.text
+			.lines()
+			.map(|l| measure_text(l, None, self.font_size as u16, 1.).width)
+			.fold(0., f32::max);
+		vec2(width, lines * self.font_size)
+	}
+	fn activation_factor(&mut self) -> f32 { 0. }
+	fn get_id(&self) -> String { self.text.clone() }
+	fn update(&mut self) {}
+	fn draw(&self) {
+		for (i, line) in self.text.lines().enumerate() {
+			draw_text(
+				line,
+				self.position.x,
+				self.position.y + (i + 1) as f32 * self.font_size,
+				self.font_size,
+				WHITE
+			);
+		}
+	}
+	fn get_roles(&self) -> Vec::<SpecialRole> { Vec::new() }
+}
+
 pub struct Button {
 	position: Vec2,
 	size: Vec2,
 	title: String,
+	label: Option<String>,
 	activated: f32,
 	hovered: bool,
 	pressed: bool,
@@ -174,8 +226,7 @@ impl Widget for Button {
 			}
 		);
 		draw_text(
-			&self.title.chars().take_while(|x| *x!='.')
-				.collect::<String>(),
+			self.label.as_ref().unwrap_or(&self.title),
 			self.position.x + 10.,
 			self.position.y + self.size.y * 0.8,
 			self.size.y * 0.6,
@@ -194,6 +245,7 @@ impl Button {
 			position,
 			size,
 			title: String::new(),
+			label: None,
 			activated: 0.,
 			hovered: false,
 			pressed: false,
@@ -206,6 +258,12 @@ impl Button {
 		self
 	}
 
+	/// Text displayed instead of the title
+	pub fn label(mut self, x: &str) -> Self {
+		self.label = Some(x.to_string());
+		self
+	}
+
 	pub fn role(mut self, r: SpecialRole) -> Self {
 		self.roles.push(r);
 		self
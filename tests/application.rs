@@ -0,0 +1,16 @@
+//! Errors stay on screen until the player leaves them
+
+use runner::application::ErrorScreen;
+
+#[test]
+fn failed_reloads_keep_their_message() {
+	let mut error = ErrorScreen::default();
+
+	// Reloading fails while playing, which switches to the error screen
+	error.show("error: cannot read level `broken.lvl`".to_string());
+	error.state_changed("Play");
+	assert_eq!(error.message(), Some("error: cannot read level `broken.lvl`"));
+
+	error.state_changed(ErrorScreen::STATE);
+	assert_eq!(error.message(), None);
+}
@@ -0,0 +1,70 @@
+//! Command line parsing and level checking
+
+use macroquad::math::vec2;
+use runner::cli::*;
+use runner::game::world::Start;
+
+fn args(s: &str) -> Vec::<String> {
+	s.split_whitespace().map(str::to_owned).collect()
+}
+
+fn command(s: &str) -> Result<Command, String> {
+	Arguments::parse(args(s)).map(|a| a.command)
+}
+
+#[test]
+fn parses_commands() {
+	assert_eq!(command(""), Ok(Command::Play(None, None)));
+	assert_eq!(command("check a.lvl b.lvl"), Ok(Command::Check(args("a.lvl b.lvl"))));
+	assert_eq!(command("--help"), Ok(Command::Help));
+	assert!(command("check").is_err());
+	assert!(command("fly").is_err());
+}
+
+#[test]
+fn parses_launch_options() {
+	assert_eq!(command("--level parkour.lvl"), Ok(Command::Play(Some("parkour.lvl".to_owned()), None)));
+	assert_eq!(
+		command("--level parkour.lvl --checkpoint 2"),
+		Ok(Command::Play(Some("parkour.lvl".to_owned()), Some(Start::Checkpoint(2))))
+	);
+	assert_eq!(
+		command("--at 64,-32.5 --level parkour.lvl"),
+		Ok(Command::Play(Some("parkour.lvl".to_owned()), Some(Start::Position(vec2(64., -32.5)))))
+	);
+	assert_eq!(
+		Arguments::parse(args("--levels-dir my/levels check a.lvl")),
+		Ok(Arguments {command: Command::Check(args("a.lvl")), levels_dir: Some("my/levels".to_owned())})
+	);
+
+	assert!(command("--checkpoint 2").is_err());
+	assert!(command("--level parkour.lvl --at 64").is_err());
+	assert!(command("--level parkour.lvl --checkpoint two").is_err());
+	assert!(command("--level").is_err());
+	assert!(command("--level a.lvl check a.lvl").is_err());
+	assert!(command("--fly").is_err());
+}
+
+#[test]
+fn shipped_levels_pass_the_check() {
+	assert_eq!(check(&args("res/levels/little.lvl res/levels/parkour.lvl res/levels/lab.lvl")), 0);
+}
+
+#[test]
+fn broken_levels_fail_the_check() {
+	let broken = std::env::temp_dir().join("runner-check-broken.lvl");
+	std::fs::write(&broken, "Wall at (1, 2").unwrap();
+	let broken = broken.to_str().unwrap().to_owned();
+
+	assert_eq!(check(&[broken, "res/levels/little.lvl".to_owned(), "missing.lvl".to_owned()]), 2);
+}
+
+#[test]
+fn includes_are_relative_to_the_checked_file() {
+	let directory = std::env::temp_dir().join(format!("runner-check-{}", std::process::id()));
+	std::fs::create_dir_all(directory.join("prefabs")).unwrap();
+	std::fs::write(directory.join("prefabs/block.lvl"), "prefab Block {\n\tWall at (0, 0) of size (2, 2)\n}").unwrap();
+	std::fs::write(directory.join("inc.lvl"), "include \"prefabs/block.lvl\"\nPlayer at (0, 0)\nplace Block at (3, 1)").unwrap();
+
+	assert_eq!(check(&[directory.join("inc.lvl").to_str().unwrap().to_owned()]), 0);
+}
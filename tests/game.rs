@@ -0,0 +1,84 @@
+//! Runs reported to the statistics and raced against
+
+use std::sync::Once;
+
+use macroquad::prelude::vec2;
+
+use runner::game::Game;
+use runner::game::headless::block_on;
+use runner::game::input::*;
+use runner::game::replay::Replay;
+use runner::game::resource_manager::ResourceManager;
+use runner::game::world::{World, RunStatus, Start};
+
+/// Copies of little.lvl in a temporary directory, where completed runs save their replay
+fn levels() {
+	static LEVELS: Once = Once::new();
+	LEVELS.call_once(|| {
+		let directory = std::env::temp_dir().join(format!("runner-game-{}", std::process::id()));
+		std::fs::create_dir_all(&directory).unwrap();
+		for level in ["little.lvl", "raced.lvl", "unraced.lvl"] {
+			std::fs::copy("res/levels/little.lvl", directory.join(level)).unwrap();
+		}
+		assert!(World::set_levels_dir(directory.to_str().unwrap()));
+	});
+}
+
+/// Jumps over the spike of little.lvl
+fn winning_run() -> ScriptedInput {
+	ScriptedInput::new()
+		.then(InputState::default(), 20)
+		.then(InputState { jump: true }, 1)
+}
+
+/// Plays little.lvl jumping over its spike, from `start` if any
+fn play(start: Option<Start>) -> Game {
+	levels();
+	let mut game = block_on(Game::new("little.lvl", &mut ResourceManager::null()))
+		.unwrap()
+		.input(winning_run());
+	if let Some(start) = start {
+		game.start_at(start).unwrap();
+	}
+	for _ in 0..600 {
+		game.advance(World::TICK);
+	}
+	assert!(game.is_complete());
+	game
+}
+
+#[test]
+fn runs_from_the_beginning_are_reported() {
+	let report = play(None).take_report().unwrap();
+	assert_eq!(report.status, RunStatus::Complete);
+}
+
+#[test]
+fn runs_from_elsewhere_are_not_reported() {
+	assert!(play(Some(Start::Position(vec2(8. * 32., 0.)))).take_report().is_none());
+}
+
+#[test]
+fn ghosts_replay_the_best_run() {
+	levels();
+	let hash = block_on(World::from(("raced.lvl", &mut ResourceManager::null()))).unwrap().hash();
+	let mut ticks = vec![InputState::default(); 20];
+	ticks.push(InputState { jump: true });
+	Replay::new("raced.lvl", hash, ticks).save(World::levels_dir()).unwrap();
+
+	let mut game = block_on(Game::new("raced.lvl", &mut ResourceManager::null())).unwrap().input(winning_run());
+	assert!(game.ghost().is_some());
+	for _ in 0..600 {
+		game.advance(World::TICK);
+		let ghost = game.ghost().unwrap().player().unwrap().position;
+		assert_eq!(ghost, game.world().player().unwrap().position);
+	}
+	assert!(game.is_complete());
+}
+
+#[test]
+fn no_ghost_without_replay() {
+	levels();
+	let game = block_on(Game::new("unraced.lvl", &mut ResourceManager::null())).unwrap();
+	assert!(game.ghost().is_none());
+}
@@ -0,0 +1,69 @@
+//! Editor commands can be undone and redone
+
+use macroquad::prelude::*;
+
+use runner::editor::history::*;
+use runner::game::world::blueprint::*;
+
+fn blueprint() -> Blueprint {
+	let mut r = Blueprint::new(vec2(32., 32.));
+	r.pieces.push(Piece::new("Player", Vec2::ZERO, vec2(32., 32.)));
+	r
+}
+
+#[test]
+fn undo_and_redo_every_command() {
+	let original = blueprint();
+	let mut edited = original.clone();
+	let mut history = History::new();
+
+	let commands = [
+		Command::Add(1, Piece::new("Wall", vec2(0., 32.), vec2(320., 32.))),
+		Command::Move {piece: 1, from: vec2(0., 32.), to: vec2(32., 64.)},
+		Command::Resize {piece: 1, from: vec2(320., 32.), to: vec2(64., 64.)},
+		Command::ChangeKind {piece: 1, from: "Wall".to_owned(), to: "Spike".to_owned()},
+		Command::ChangeUnit {from: vec2(32., 32.), to: vec2(16., 16.)},
+		Command::Remove(0, Piece::new("Player", Vec2::ZERO, vec2(32., 32.)))
+	];
+	let mut states = vec![edited.clone()];
+	for command in commands {
+		history.execute(command, &mut edited);
+		states.push(edited.clone());
+	}
+	assert_eq!(edited.pieces, vec![Piece::new("Spike", vec2(32., 64.), vec2(64., 64.))]);
+	assert_eq!(edited.unit, vec2(16., 16.));
+
+	for state in states.iter().rev().skip(1) {
+		assert!(history.undo(&mut edited));
+		assert_eq!(&edited, state);
+	}
+	assert!(!history.undo(&mut edited));
+	assert_eq!(edited, original);
+
+	for state in states.iter().skip(1) {
+		assert!(history.redo(&mut edited));
+		assert_eq!(&edited, state);
+	}
+	assert!(!history.redo(&mut edited));
+}
+
+#[test]
+fn new_command_clears_redo() {
+	let mut edited = blueprint();
+	let mut history = History::new();
+
+	history.execute(Command::Move {piece: 0, from: Vec2::ZERO, to: vec2(32., 0.)}, &mut edited);
+	history.undo(&mut edited);
+	assert!(history.can_redo());
+
+	history.execute(Command::Move {piece: 0, from: Vec2::ZERO, to: vec2(64., 0.)}, &mut edited);
+	assert!(!history.can_redo());
+	assert_eq!(edited.pieces[0].position, vec2(64., 0.));
+}
+
+#[test]
+fn noop_commands_are_not_recorded() {
+	let mut history = History::new();
+	history.record(Command::Move {piece: 0, from: Vec2::ZERO, to: Vec2::ZERO});
+	assert!(!history.can_undo());
+}
@@ -0,0 +1,477 @@
+//! Headless runs of the shipped levels
+
+use macroquad::prelude::Vec2;
+
+use runner::game::headless::*;
+use runner::game::input::*;
+use runner::game::world::{World, RunStatus, LevelLoadError, Start};
+use runner::game::resource_manager::ResourceManager;
+use runner::game::world::diagnostic::Diagnostic;
+
+fn levels() -> Vec::<String> {
+	std::fs::read_dir("res/levels")
+		.unwrap()
+		.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+		.filter(|name| name.ends_with(".lvl"))
+		.collect()
+}
+
+#[test]
+fn every_level_loads() {
+	for level in levels() {
+		if let Err(e) = Simulation::load(&level) {
+			panic!("{e}");
+		}
+	}
+}
+
+#[test]
+fn every_level_ends() {
+	for level in levels() {
+		let mut simulation = Simulation::load(&level).unwrap();
+		simulation.run(60 * 60);
+		assert_ne!(simulation.status(), RunStatus::Playing, "{level} never ends");
+	}
+}
+
+#[test]
+fn player_runs_at_initial_speed() {
+	let mut simulation = Simulation::load("little.lvl").unwrap();
+	simulation.step(10);
+	assert_eq!(simulation.player_position().unwrap().x, 50.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+#[test]
+fn little_is_lost_without_jumping() {
+	let mut simulation = Simulation::load("little.lvl").unwrap();
+	simulation.run(600);
+	assert_eq!(simulation.status(), RunStatus::Dead);
+	assert!(simulation.player_position().unwrap().x < 5. * 32.);
+}
+
+#[test]
+fn little_is_won_by_jumping_over_the_spike() {
+	let mut simulation = Simulation::load("little.lvl")
+		.unwrap()
+		.input(
+			ScriptedInput::new()
+				.then(InputState::default(), 20)
+				.then(InputState { jump: true }, 1)
+		);
+	simulation.run(600);
+	assert_eq!(simulation.status(), RunStatus::Complete);
+}
+
+#[test]
+fn broken_level_is_an_error() {
+	match Simulation::from_source("broken.lvl", "Wall at (1, 2") {
+		Err(LevelLoadError::Tokenizer {..}) => {},
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("broken level loaded")
+	}
+}
+
+#[test]
+fn initial_speed_can_be_a_vector() {
+	let mut simulation = Simulation::from_source(
+		"launch.lvl",
+		"Unit is (32, 32)\nPlayer at (0, 0) with initial speed of (0.25, -0.5)"
+	).unwrap();
+	simulation.step(1);
+	assert_eq!(simulation.player_position().unwrap(), Vec2::new(8., -16.));
+}
+
+#[test]
+fn gravity_can_be_overridden() {
+	let mut simulation = Simulation::from_source("moon.lvl", "Player at (0, 0) with gravity of 0.5px").unwrap();
+	simulation.step(2);
+	assert_eq!(simulation.player_position().unwrap().y, 0.5);
+}
+
+#[test]
+fn constants_and_arithmetic() {
+	let literal = Simulation::from_source("literal.lvl", "Unit is (32, 32)\nPlayer at (0, 0)\nWall at (-1, 4.5) of size (6, 2)").unwrap();
+	let computed = Simulation::from_source(
+		"computed.lvl",
+		"let size = 32\nUnit is (size, size)\nlet gap = 3\nlet offset = (gap - 1) / 4\nPlayer at (0, 0)\nWall at (-1, gap + 1 + offset) of size (2 * gap, 2)"
+	).unwrap();
+	assert_eq!(computed.world().to_string(), literal.world().to_string());
+}
+
+#[test]
+fn scalars_can_be_expressions() {
+	let literal = Simulation::from_source(
+		"literal.lvl",
+		"Player at (0, 0) with initial speed of 3 with gravity of 0.5\nrepeat 2 times step (1, 0) {\n\tWall at (0, 1)\n}"
+	).unwrap();
+	let computed = Simulation::from_source(
+		"computed.lvl",
+		"let n = 2\nlet gap = 3\nlet g = 0.5\nPlayer at (0, 0) with initial speed of gap + 0 with gravity of g\nrepeat n times step (1, 0) {\n\tWall at (0, 1)\n}"
+	).unwrap();
+	assert_eq!(computed.world().to_string(), literal.world().to_string());
+}
+
+#[test]
+fn misspelled_keywords_are_named() {
+	match Simulation::from_source("typo.lvl", "Wal at (0, 1)") {
+		Err(LevelLoadError::Interpretor {error, ..}) => assert!(error.message().starts_with("unexpected `Wal`, expected")),
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("typo loaded")
+	}
+}
+
+#[test]
+fn bindings_end_with_their_block() {
+	let literal = Simulation::from_source(
+		"literal.lvl",
+		"Wall at (5, 0)\nWall at (1, 1)\nWall at (1, 2)\nWall at (2, 2)\nWall at (0, 3)"
+	).unwrap();
+	let scoped = Simulation::from_source(
+		"scoped.lvl",
+		"let n = 1\nlet x = 0\nprefab P {\n\tlet n = 5\n\tWall at (n, 0)\n}\nplace P at (0, 0)\nWall at (n, 1)\nrepeat 2 times step (0, 0) {\n\tlet x = x + 1\n\tWall at (x, 2)\n}\nWall at (x, 3)"
+	).unwrap();
+	assert_eq!(scoped.world().to_string(), literal.world().to_string());
+}
+
+#[test]
+fn bindings_have_no_unit() {
+	match Simulation::from_source("unit.lvl", "let w = 3px\nWall at (w, 0)") {
+		Err(LevelLoadError::Interpretor {error, ..}) => {
+			assert_eq!(error.message(), "constant `w` cannot have a unit");
+			assert_eq!(error.span().column, 9);
+		},
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("binding with a unit loaded")
+	}
+}
+
+#[test]
+fn bad_expressions_are_errors() {
+	for source in ["Wall at (gap, 0)", "let zero = 0\nWall at (1 / zero, 0)", "Wall at (1 +, 0)"] {
+		if Simulation::from_source("bad.lvl", source).is_ok() {
+			panic!("{source} loaded");
+		}
+	}
+}
+
+#[test]
+fn repeat_blocks_nest() {
+	let literal = Simulation::from_source(
+		"literal.lvl",
+		"Player at (0, 0)\nSpike at (0, 2)\nSpike at (1, 2)\nWall at (0, 3)\nSpike at (4, 2)\nSpike at (5, 2)\nWall at (4, 3)"
+	).unwrap();
+	let repeated = Simulation::from_source(
+		"repeated.lvl",
+		"Player at (0, 0)\nrepeat 2 times step (4, 0) {\n\trepeat 2 times step (1, 0) {\n\t\tSpike at (0, 2)\n\t}\n\tWall at (0, 3)\n}\nrepeat 0 times step (1, 0) {\n\tWall at (0, 0)\n}"
+	).unwrap();
+	assert_eq!(repeated.world().to_string(), literal.world().to_string());
+}
+
+#[test]
+fn indented_comments_are_ignored() {
+	let literal = Simulation::from_source("literal.lvl", "Wall at (0, 0)\nWall at (1, 0)").unwrap();
+	let commented = Simulation::from_source(
+		"commented.lvl",
+		"repeat 2 times step (1, 0) {\n\t# One wall per iteration\n\tWall at (0, 0)\n  # Indented with spaces\n}"
+	).unwrap();
+	assert_eq!(commented.world().to_string(), literal.world().to_string());
+}
+
+#[test]
+fn errors_in_loops_report_the_iteration() {
+	let source = "let d = 3\nrepeat 5 times step (1, 0) {\n\tlet d = d - 1\n\tWall at (1 / d, 0)\n}";
+	match Simulation::from_source("loop.lvl", source) {
+		Err(LevelLoadError::Interpretor {error, ..}) => {
+			assert_eq!(error.message(), "division by zero (iteration 3 of 5 of the loop at line 2)");
+			assert_eq!(error.span().line, 4);
+		},
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("division by zero loaded")
+	}
+}
+
+#[test]
+fn huge_loops_are_errors() {
+	for source in [
+		"repeat 100000000 times step (1, 0) { Wall at (0, 1) }",
+		"repeat 1000 times step (1, 0) {\n\trepeat 1000 times step (0, 1) { Wall at (0, 1) }\n}"
+	] {
+		match Simulation::from_source("huge.lvl", source) {
+			Err(e @ LevelLoadError::Interpretor {..}) => assert!(e.to_string().contains("more than 100000 times"), "{e}"),
+			Err(e) => panic!("{e}"),
+			Ok(_) => panic!("{source} should not load")
+		}
+	}
+	assert!(Simulation::from_source("big.lvl", "repeat 1000 times step (1, 0) { Wall at (0, 1) }").is_ok());
+}
+
+#[test]
+fn unclosed_block_is_an_error() {
+	assert!(Simulation::from_source("unclosed.lvl", "repeat 2 times step (1, 0) {\nWall at (0, 0)").is_err());
+	assert!(Simulation::from_source("unclosed.lvl", "repeat 0 times step (1, 0) {\nWall at (0, 0)").is_err());
+	assert!(Simulation::from_source("unopened.lvl", "Wall at (0, 0)\n}").is_err());
+}
+
+#[test]
+fn prefabs_are_placed_relative_to_their_position() {
+	let literal = Simulation::from_source("literal.lvl", "Unit is (50, 50)\nWall at (10, 2) of size (2, 9)\nWall at (12, 3) of size (2, 8)\nWall at (14, 4) of size (2, 7)\nWall at (16, 5) of size (2, 6)\nWall at (18, 6) of size (2, 5)\nSpike at (1, 1)\nSpike at (3, 1)").unwrap();
+	let placed = Simulation::from_source(
+		"placed.lvl",
+		"Unit is (50, 50)\ninclude \"prefabs/stairs.lvl\"\nplace Stairs at (10, 2)\nprefab Trap {\n\tSpike at (0, 0)\n}\nplace Trap at (1, 1)\nplace Trap at (3, 1)"
+	).unwrap();
+	assert_eq!(placed.world().to_string(), literal.world().to_string());
+}
+
+#[test]
+fn errors_in_prefabs_report_the_placement() {
+	match Simulation::from_source("prefab.lvl", "prefab Broken {\n\tWall at (gap, 0)\n}\nplace Broken at (1, 1)") {
+		Err(LevelLoadError::Interpretor {error, ..}) => {
+			assert_eq!(error.message(), "unknown constant `gap` (in prefab `Broken` placed at line 4)");
+			assert_eq!(error.span().line, 2);
+		},
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("broken prefab loaded")
+	}
+	assert!(Simulation::from_source("prefab.lvl", "place Missing at (0, 0)").is_err());
+	assert!(Simulation::from_source("prefab.lvl", "prefab Loop {\n\tplace Loop at (1, 0)\n}\nplace Loop at (0, 0)").is_err());
+}
+
+#[test]
+fn include_errors_report_the_chain() {
+	match Simulation::from_source("main.lvl", "include \"main.lvl\"") {
+		Err(LevelLoadError::IncludeCycle(chain)) => assert_eq!(chain, ["main.lvl", "main.lvl"]),
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("include cycle loaded")
+	}
+	match Simulation::from_source("main.lvl", "include \"prefabs/missing.lvl\"") {
+		Err(LevelLoadError::Included {chain, error}) => {
+			assert_eq!(chain, ["main.lvl", "prefabs/missing.lvl"]);
+			assert!(matches!(*error, LevelLoadError::Io(..)));
+		},
+		Err(e) => panic!("unexpected error {e}"),
+		Ok(_) => panic!("missing include loaded")
+	}
+}
+
+#[test]
+fn invalid_metadata_is_an_error() {
+	for source in ["Background is \"blue\"", "Difficulty is 1.5", "Title is 3", "Difficulty is \"hard\""] {
+		if Simulation::from_source("metadata.lvl", source).is_ok() {
+			panic!("{source} loaded");
+		}
+	}
+}
+
+#[test]
+fn starts_at_a_checkpoint() {
+	let mut world = block_on(World::from(("lab.lvl", &mut ResourceManager::null()))).unwrap();
+	world.start_at("lab.lvl", Start::Checkpoint(1)).unwrap();
+
+	let player = world.player().unwrap();
+	assert_eq!(player.position.x, 28. * 50.);
+	assert_eq!(player.position.y + player.size.y, 6. * 50.);
+	assert!(world.has_checkpoint());
+
+	assert!(matches!(
+		world.start_at("lab.lvl", Start::Checkpoint(2)),
+		Err(LevelLoadError::NoCheckpoint {checkpoint: 2, count: 1, ..})
+	));
+}
+
+#[test]
+fn starts_at_a_position() {
+	let mut world = block_on(World::from(("little.lvl", &mut ResourceManager::null()))).unwrap();
+	world.start_at("little.lvl", Start::Position(Vec2::new(100., -20.))).unwrap();
+	assert_eq!(world.player().unwrap().position, Vec2::new(100., -20.));
+	assert!(!world.has_checkpoint());
+
+	world.step(InputState::default());
+	assert!(world.player().unwrap().position.x > 100.);
+}
+
+#[test]
+fn players_ride_platforms() {
+	// The player falls on the platform during the first tick, then follows it
+	let mut simulation = Simulation::from_source(
+		"ride.lvl",
+		"Unit is (10, 10)\nPlayer at (1, -1) of size (1, 1)\nPlatform at (0, 0) of size (5, 1) moving to (20, 0) at 1px"
+	).unwrap();
+	simulation.step(50);
+	assert_eq!(simulation.player_position(), Some(Vec2::new(10. + 49., -10.)));
+	assert_eq!(simulation.status(), RunStatus::Playing);
+
+	// Going down 50px and back up by 30px
+	let mut simulation = Simulation::from_source(
+		"lift.lvl",
+		"Unit is (10, 10)\nPlayer at (1, -1) of size (1, 1)\nPlatform at (0, 0) of size (5, 1) moving to (0, 5) at 2px"
+	).unwrap();
+	simulation.step(40);
+	assert_eq!(simulation.player_position(), Some(Vec2::new(10., 20. - 10.)));
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+#[test]
+fn platform_paths_are_checked() {
+	for source in ["Platform at (0, 0) moving to (1, 0)", "Platform at (0, 0) moving to (1, 0) then at 1", "Wall at (0, 0) moving to (1, 0) at 1", "Platform at (0, 0) moving to (1, 0) at -1px", "Platform at (0, 0) moving to (1, 0) at 0"] {
+		if Simulation::from_source("path.lvl", source).is_ok() {
+			panic!("{source} should not load");
+		}
+	}
+}
+
+#[test]
+fn jump_pads_launch_the_player() {
+	let mut simulation = Simulation::from_source(
+		"pad.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 0) of size (1, 1)\nJumpPad at (0, 1)\nWall at (0, 2) of size (5, 1)"
+	).unwrap();
+
+	let mut highest = f32::MAX;
+	for _ in 0..30 {
+		simulation.step(1);
+		highest = highest.min(simulation.player_position().unwrap().y);
+	}
+	// Four player heights above the pad
+	assert!(highest < -25., "{highest}");
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+#[test]
+fn gravity_portals_let_players_run_on_ceilings() {
+	let mut simulation = Simulation::from_source(
+		"flip.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 5) of size (1, 1) with initial speed of 2px\nWall at (0, 0) of size (50, 1)\nGravityPortal at (0, 3) of size (2, 4)"
+	).unwrap();
+	simulation.step(30);
+
+	assert_eq!(simulation.player_position().unwrap().y, 10.);
+	assert!(simulation.world().player().unwrap().get_gravity() < 0.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+#[test]
+fn speed_portals_set_the_speed() {
+	let mut simulation = Simulation::from_source(
+		"speed.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 0) of size (1, 1) with initial speed of 2px\nWall at (0, 1) of size (100, 1)\nSpeedPortal at (5, 0) with speed of 5px"
+	).unwrap();
+	simulation.step(10);
+	assert_eq!(simulation.world().player().unwrap().get_speed().x, 2.);
+	simulation.step(20);
+	assert_eq!(simulation.world().player().unwrap().get_speed().x, 5.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+#[test]
+fn speed_portals_need_a_positive_speed() {
+	for source in ["SpeedPortal at (0, 0) with speed of 0px", "SpeedPortal at (0, 0) with speed of -2"] {
+		match Simulation::from_source("portal.lvl", source) {
+			Err(e) => assert!(e.to_string().contains("expected a positive number"), "{e}"),
+			Ok(_) => panic!("{source} should not load")
+		}
+	}
+}
+
+#[test]
+fn one_way_platforms_are_only_solid_from_above() {
+	// Jumping through from below, then landing on top
+	let mut simulation = Simulation::from_source(
+		"through.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 3) of size (1, 1) with initial speed of (0.2, -1.5)\nOneWayPlatform at (0, 1) of size (10, 1)"
+	).unwrap();
+	simulation.step(40);
+	assert_eq!(simulation.player_position().unwrap().y, 0.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+
+	// Running through from the side
+	let mut simulation = Simulation::from_source(
+		"side.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 1) of size (1, 1) with initial speed of 2px\nWall at (0, 2) of size (50, 1)\nOneWayPlatform at (3, 1) of size (2, 1)"
+	).unwrap();
+	simulation.step(40);
+	assert!(simulation.player_position().unwrap().x > 50.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+/// Left and bottom of a 10px high player after each tick
+fn trajectory(simulation: &mut Simulation, ticks: usize) -> Vec::<(f32, f32)> {
+	(0..ticks).map(|_| {
+		simulation.step(1);
+		let p = simulation.player_position().unwrap();
+		(p.x, p.y + 10.)
+	}).collect()
+}
+
+#[test]
+fn players_run_up_and_down_slopes() {
+	// Rising by 20px over 40px, to a plateau
+	let mut simulation = Simulation::from_source(
+		"up.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 1) of size (1, 1) with initial speed of 2px\nWall at (0, 2) of size (10, 1)\nSlopeUp at (3, 0) of size (4, 2)\nWall at (7, 0) of size (10, 3)"
+	).unwrap();
+	for (x, bottom) in trajectory(&mut simulation, 40) {
+		// Right corner on the slope
+		if x + 10. > 30. && x + 10. < 70. {
+			assert!((bottom - (20. - (x + 10. - 30.) / 2.)).abs() < 1e-3, "{x} {bottom}");
+		}
+	}
+	assert_eq!(simulation.player_position().unwrap().y, -10.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+
+	// Descending faster than gravity alone would
+	let mut simulation = Simulation::from_source(
+		"down.lvl",
+		"Unit is (10, 10)\nPlayer at (0, -1) of size (1, 1) with initial speed of 4px\nWall at (0, 0) of size (3, 1)\nSlopeDown at (3, 0) of size (4, 2)\nWall at (7, 2) of size (10, 1)"
+	).unwrap();
+	for (x, bottom) in trajectory(&mut simulation, 25) {
+		// Left corner on the slope
+		if x > 30. && x < 70. {
+			assert!((bottom - (x - 30.) / 2.).abs() < 1e-3, "{x} {bottom}");
+		}
+	}
+	assert_eq!(simulation.player_position().unwrap().y, 10.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
+
+#[test]
+fn slopes_are_solid_from_their_sides() {
+	let mut simulation = Simulation::from_source(
+		"cliff.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 1) of size (1, 1) with initial speed of 2px\nWall at (0, 2) of size (10, 1)\nSlopeDown at (3, 0) of size (4, 2)"
+	).unwrap();
+	simulation.run(60);
+	assert_eq!(simulation.status(), RunStatus::Dead);
+}
+
+#[test]
+fn jumps_off_slopes_follow_their_angle() {
+	let jump = |source: &str| {
+		let mut simulation = Simulation::from_source("jump.lvl", source)
+			.unwrap()
+			.input(
+				ScriptedInput::new()
+					.then(InputState::default(), 19)
+					.then(InputState { jump: true }, 1)
+			);
+		simulation.step(20);
+		simulation.world().player().unwrap().get_speed().y
+	};
+
+	let flat = jump("Unit is (10, 10)\nPlayer at (0, 1) of size (1, 1) with initial speed of 2px\nWall at (0, 2) of size (10, 1)");
+	let slope = jump("Unit is (10, 10)\nPlayer at (0, 1) of size (1, 1) with initial speed of 2px\nWall at (0, 2) of size (10, 1)\nSlopeUp at (3, 0) of size (4, 2)");
+	// Rising by 1px per tick along the slope
+	assert!((slope - (flat - 1.)).abs() < 1e-3, "{flat} {slope}");
+}
+
+#[test]
+fn flipped_players_run_under_slopes() {
+	let mut simulation = Simulation::from_source(
+		"under.lvl",
+		"Unit is (10, 10)\nPlayer at (0, 5) of size (1, 1) with initial speed of 2px\nSlopeUp at (0, 0) of size (50, 1)\nGravityPortal at (0, 3) of size (2, 4)"
+	).unwrap();
+	simulation.step(30);
+
+	assert_eq!(simulation.player_position().unwrap().y, 10.);
+	assert_eq!(simulation.status(), RunStatus::Playing);
+}
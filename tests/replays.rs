@@ -0,0 +1,70 @@
+//! Replays saved to disk play back exactly
+
+use runner::game::headless::*;
+use runner::game::input::*;
+use runner::game::replay::*;
+use runner::game::world::RunStatus;
+
+fn directory(name: &str) -> String {
+	let d = std::env::temp_dir().join(format!("runner-{name}-{}", std::process::id()));
+	std::fs::create_dir_all(&d).unwrap();
+	d.to_str().unwrap().to_owned()
+}
+
+fn winning_run() -> Vec::<InputState> {
+	let mut ticks = vec![InputState::default(); 20];
+	ticks.push(InputState { jump: true });
+	ticks.extend(vec![InputState::default(); 60]);
+	ticks
+}
+
+#[test]
+fn replay_plays_back_the_recorded_run() {
+	let directory = directory("playback");
+
+	let mut live = Simulation::load("little.lvl").unwrap().input(ScriptedInput::from(winning_run()));
+	let ticks = live.run(600);
+	assert_eq!(live.status(), RunStatus::Complete);
+	let hash = live.world().hash();
+
+	Replay::new("little.lvl", hash, winning_run()).save(&directory).unwrap();
+	let replay = Replay::load(&directory, "little.lvl", hash).unwrap();
+	assert_eq!(replay.len(), winning_run().len());
+
+	let mut watched = Simulation::load("little.lvl").unwrap().input(replay.input());
+	assert_eq!(watched.run(600), ticks);
+	assert_eq!(watched.status(), RunStatus::Complete);
+	assert_eq!(watched.player_position(), live.player_position());
+}
+
+#[test]
+fn replay_of_a_modified_level_is_refused() {
+	let source = std::fs::read_to_string("res/levels/little.lvl").unwrap();
+	let directory = directory("mismatch");
+
+	let hash = Simulation::from_source("little.lvl", &source).unwrap().world().hash();
+	Replay::new("little.lvl", hash, winning_run()).save(&directory).unwrap();
+
+	let modified = Simulation::from_source("little.lvl", &format!("{source}\nSpike at (7, 0)")).unwrap().world().hash();
+	match Replay::load(&directory, "little.lvl", modified) {
+		Err(ReplayError::LevelMismatch) => {},
+		r => panic!("expected a level mismatch, got {r:?}")
+	}
+}
+
+#[test]
+fn replay_of_a_level_whose_prefab_changed_is_refused() {
+	let level = std::fs::read_to_string("res/levels/lab.lvl").unwrap();
+	let prefab = std::fs::read_to_string("res/levels/prefabs/stairs.lvl").unwrap();
+	let directory = directory("prefab");
+
+	let hash = Simulation::load("lab.lvl").unwrap().world().hash();
+	assert_eq!(hash, Replay::hash([level.as_str(), prefab.as_str()]));
+	Replay::new("lab.lvl", hash, winning_run()).save(&directory).unwrap();
+
+	let modified = Replay::hash([level.as_str(), &format!("{prefab}\nWall at (0, 0)")]);
+	match Replay::load(&directory, "lab.lvl", modified) {
+		Err(ReplayError::LevelMismatch) => {},
+		r => panic!("expected a level mismatch, got {r:?}")
+	}
+}
@@ -0,0 +1,127 @@
+//! Round trips between worlds and their canonical source
+
+use macroquad::prelude::*;
+
+use runner::game::headless::block_on;
+use runner::game::resource_manager::ResourceManager;
+use runner::game::world::World;
+use runner::game::world::blueprint::*;
+
+fn load(level: &str, source: &str) -> World {
+	match block_on(World::from_source(level, source, &mut ResourceManager::null())) {
+		Ok(world) => world,
+		Err(e) => panic!("{e}\n{source}")
+	}
+}
+
+#[test]
+fn every_level_round_trips() {
+	for entry in std::fs::read_dir("res/levels").unwrap() {
+		let level = entry.unwrap().file_name().into_string().unwrap();
+		if !level.ends_with(".lvl") {
+			continue;
+		}
+
+		let world = load(&level, &World::read(&level).unwrap());
+		let source = world.to_string();
+		let reloaded = load(&level, &source);
+
+		assert_eq!(Blueprint::from(&world), Blueprint::from(&reloaded), "{level}");
+		assert_eq!(source, reloaded.to_string(), "{level} is not canonical");
+	}
+}
+
+#[test]
+fn keeps_initial_speed() {
+	let world = load("speed", "Unit is (32, 32)\nPlayer at (0, 0) with initial speed of 7px\n");
+	assert_eq!(world.to_string(), "Unit is (32, 32)\nPlayer at (0, 0) with initial speed of 7px\n");
+}
+
+#[test]
+fn keeps_declaration_order() {
+	// The player does not collide with the wall declared before it
+	let source = "Unit is (32, 32)\nWall at (0, 1)\nPlayer at (0, 0)\n";
+	assert_eq!(load("order", source).to_string(), source);
+}
+
+#[test]
+fn writes_short_vector_speeds() {
+	// Written in pixels because of the wall
+	let world = load("launch", "Unit is (50, 50)\nPlayer at (0, 0) with initial speed of (0.3, -0.1)\nWall at (0.01, 1)\n");
+	assert_eq!(world.to_string(), "Unit is (1, 50)\nPlayer at (0, 0) of size (50, 1) with initial speed of (15, -0.1)\nWall at (0.5, 1) of size (50, 1)\n");
+}
+
+#[test]
+fn falls_back_to_pixels() {
+	let mut blueprint = Blueprint::new(vec2(32., 32.));
+	blueprint.pieces.push(Piece::new("Wall", vec2(5., 64.), vec2(32., 32.)));
+	assert_eq!(blueprint.infer_unit(), vec2(1., 32.));
+	assert_eq!(blueprint.to_string(), "Unit is (1, 32)\nWall at (5, 2) of size (32, 1)\n");
+}
+
+#[test]
+fn infers_common_unit() {
+	let mut blueprint = Blueprint::new(vec2(7., 7.));
+	blueprint.pieces.push(Piece::new("Wall", vec2(20., 0.), vec2(40., 10.)));
+	assert_eq!(blueprint.infer_unit(), vec2(20., 10.));
+}
+
+#[test]
+fn signed_and_fractional_literals() {
+	let source = "Unit is (32, 32)\nPlayer at (-2, 0) with initial speed of 2.5px\nWall at (-2.5, 1) of size (0.5, 1)\n";
+	let world = load("signed", source);
+	let blueprint = Blueprint::from(&world);
+
+	assert_eq!(blueprint.pieces[0].position, vec2(-64., 0.));
+	assert_eq!(blueprint.pieces[0].speed, vec2(2.5, 0.));
+	assert_eq!(blueprint.pieces[1].position, vec2(-80., 32.));
+	assert_eq!(blueprint.pieces[1].size, vec2(16., 32.));
+
+	assert_eq!(Blueprint::from(&load("signed", &world.to_string())).pieces, blueprint.pieces);
+}
+
+#[test]
+fn keeps_launch_and_gravity() {
+	let source = "Unit is (32, 32)\nPlayer at (0, 0) with initial speed of (0.5, -1) with gravity of 0.5px\n";
+	assert_eq!(load("moon", source).to_string(), source);
+}
+
+#[test]
+fn keeps_metadata() {
+	let source = "Title is \"Up and down\"\nAuthor is \"Someone\"\nDifficulty is 3\nMusic is \"theme.wav\"\nBackground is \"#20a0ff\"\nUnit is (32, 32)\nPlayer at (0, 0)\n";
+	let world = load("metadata", source);
+	let metadata = world.metadata();
+
+	assert_eq!(metadata.title.as_deref(), Some("Up and down"));
+	assert_eq!(metadata.author.as_deref(), Some("Someone"));
+	assert_eq!(metadata.difficulty, Some(3));
+	assert_eq!(metadata.music.as_deref(), Some("theme.wav"));
+	assert_eq!(metadata.background, Color::from_rgba(0x20, 0xa0, 0xff, 255));
+	assert_eq!(world.to_string(), source);
+}
+
+#[test]
+fn untitled_levels_use_their_file_name() {
+	let world = load("untitled.lvl", "Player at (0, 0)");
+	assert_eq!(world.metadata().title_or("untitled.lvl"), "untitled");
+	assert_eq!(world.metadata().background, BLACK);
+}
+
+#[test]
+fn keeps_platform_paths() {
+	let source = "Unit is (32, 32)\nPlayer at (0, 0)\nPlatform at (2, 3) of size (3, 1) moving to (6, 3) then (6, 1) at 2px looping\nPlatform at (0, 4)\n";
+	let world = load("platforms", source);
+	assert_eq!(world.to_string(), source);
+
+	// Waypoints follow the platform when it is moved
+	let mut blueprint = Blueprint::from(&world);
+	assert_eq!(blueprint.pieces[1].route.as_ref().unwrap().waypoints, vec![vec2(128., 0.), vec2(128., -64.)]);
+	blueprint.pieces[1].position += vec2(32., 0.);
+	assert!(blueprint.to_string().contains("Platform at (3, 3) of size (3, 1) moving to (7, 3) then (7, 1) at 2px looping"));
+}
+
+#[test]
+fn keeps_portal_speed() {
+	let source = "Unit is (32, 32)\nPlayer at (0, 0)\nJumpPad at (2, 1)\nGravityPortal at (4, 0) of size (1, 2)\nSpeedPortal at (6, 0) of size (1, 2) with speed of 7px\nSpeedPortal at (8, 0)\nOneWayPlatform at (10, 2) of size (3, 1)\nSlopeUp at (13, 0) of size (2, 2)\nSlopeDown at (15, 0) of size (2, 2)\n";
+	assert_eq!(load("portals", source).to_string(), source);
+}
@@ -0,0 +1,32 @@
+//! Statistics survive a save and load cycle and stay bounded
+
+use macroquad::prelude::*;
+use runner::game::statistics::*;
+
+#[test]
+fn statistics_round_trip() {
+	let mut statistics = Statistics::default();
+	let parkour = statistics.level_mut("parkour.lvl");
+	parkour.attempts = 12;
+	parkour.best_time = Some(8.5);
+	parkour.add_death(vec2(640., 28.));
+	statistics.level_mut("little.lvl").attempts = 1;
+
+	let path = std::env::temp_dir()
+		.join(format!("runner-statistics-{}", std::process::id()))
+		.join("statistics.txt");
+	statistics.save_to(&path).unwrap();
+
+	assert_eq!(Statistics::load_from(&path).unwrap(), statistics);
+}
+
+#[test]
+fn only_the_last_deaths_are_kept() {
+	let mut stats = LevelStatistics::default();
+	for i in 0..LevelStatistics::MAX_DEATHS + 10 {
+		stats.add_death(vec2(i as f32, 0.));
+	}
+	assert_eq!(stats.death_count as usize, LevelStatistics::MAX_DEATHS + 10);
+	assert_eq!(stats.deaths.len(), LevelStatistics::MAX_DEATHS);
+	assert_eq!(stats.deaths[0], vec2(10., 0.));
+}